@@ -1,47 +1,54 @@
-use crate::loader::RtmPackage;
-use std::{fs, path::PathBuf};
+use crate::{
+    editor_state::EditorState,
+    loader::{self, RtmPackage},
+};
+use kira::{
+    dsp::Frame,
+    sound::static_sound::{StaticSoundData, StaticSoundSettings},
+};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::{fs, path::PathBuf, sync::Arc};
+
+const SYNTH_SAMPLE_RATE: u32 = 44_100;
+
+/// Frame-to-frame playhead jumps larger than this are treated as seeks or scrubs.
+const MAX_CATCH_UP_MS: u32 = 250;
 
 fn normalize_zip_path(p: &str) -> String {
     p.replace('\\', "/")
 }
 
-fn find_audio_entry_name(pkg: &RtmPackage) -> Option<String> {
-    let wanted = normalize_zip_path(pkg.meta.audioFile.trim());
-    if wanted.is_empty() {
-        return None;
-    }
-
-    if pkg.other_files.contains_key(&wanted) {
-        return Some(wanted);
-    }
-
-    let wanted_base = wanted.split('/').last().unwrap_or(&wanted);
-    for name in pkg.other_files.keys() {
-        let n = normalize_zip_path(name);
-        if n.split('/').last().unwrap_or(&n) == wanted_base {
-            return Some(name.clone());
-        }
-    }
-    None
+pub fn find_audio_entry_name(pkg: &RtmPackage) -> Option<String> {
+    loader::find_entry_name(pkg, &pkg.meta.audioFile)
 }
 
 pub fn ensure_audio_extracted(pkg: &RtmPackage, rtm_path: &PathBuf) -> anyhow::Result<Option<String>> {
     let Some(entry_name) = find_audio_entry_name(pkg) else {
         return Ok(None);
     };
+    extract_entry(pkg, rtm_path, &entry_name).map(Some)
+}
+
+/// Writes a package entry to the rtm cache so the asset server can load it. Returns the
+/// path relative to the asset root.
+pub fn extract_entry(pkg: &RtmPackage, rtm_path: &PathBuf, entry_name: &str) -> anyhow::Result<String> {
     let bytes = pkg
         .other_files
-        .get(&entry_name)
-        .ok_or_else(|| anyhow::anyhow!("audio entry not found in package: {}", entry_name))?;
+        .get(entry_name)
+        .ok_or_else(|| anyhow::anyhow!("entry not found in package: {}", entry_name))?;
 
     let stem = rtm_path
         .file_stem()
         .and_then(|s| s.to_str())
         .unwrap_or("map");
+    // The path hash keeps same-named maps from different folders from sharing a cache.
+    let mut hasher = DefaultHasher::new();
+    rtm_path.hash(&mut hasher);
     let out_path = PathBuf::from("target")
         .join("rtm_cache")
-        .join(stem)
-        .join(normalize_zip_path(&entry_name));
+        .join(format!("{}-{:016x}", stem, hasher.finish()))
+        .join(normalize_zip_path(entry_name));
 
     if let Some(parent) = out_path.parent() {
         fs::create_dir_all(parent)?;
@@ -49,5 +56,42 @@ pub fn ensure_audio_extracted(pkg: &RtmPackage, rtm_path: &PathBuf) -> anyhow::R
     fs::write(&out_path, bytes)?;
 
     let rel = out_path.to_string_lossy().replace('\\', "/");
-    Ok(Some(rel))
+    Ok(rel)
+}
+
+/// Cheap deterministic noise so synthesized sounds are the same every run.
+pub struct Noise(u32);
+
+impl Noise {
+    pub fn new(seed: u32) -> Self {
+        Noise(seed)
+    }
+
+    pub fn sample(&mut self) -> f32 {
+        self.0 = self.0.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        (self.0 >> 8) as f32 / (1u32 << 23) as f32 - 1.0
+    }
+}
+
+/// Builds a mono sound from `sample(t)`, with `t` in seconds.
+pub fn synth_sound(duration_s: f32, mut sample: impl FnMut(f32) -> f32) -> StaticSoundData {
+    let len = (duration_s * SYNTH_SAMPLE_RATE as f32) as usize;
+    let frames: Vec<Frame> = (0..len)
+        .map(|i| Frame::from_mono(sample(i as f32 / SYNTH_SAMPLE_RATE as f32).clamp(-1.0, 1.0)))
+        .collect();
+    StaticSoundData {
+        sample_rate: SYNTH_SAMPLE_RATE,
+        frames: Arc::from(frames),
+        settings: StaticSoundSettings::default(),
+    }
+}
+
+/// The span `(prev, now]` the playhead moved through since the last call, for systems that
+/// trigger sounds at map times. `None` while paused and on the frame of a seek or scrub, so
+/// whatever was jumped over stays silent.
+pub fn playback_window(last_ms: &mut Option<u32>, state: &EditorState) -> Option<(u32, u32)> {
+    let now = state.current_time;
+    let prev = last_ms.replace(now)?;
+    let continuous = now >= prev && now - prev <= MAX_CATCH_UP_MS && !state.playhead_jumped;
+    (state.is_playing && continuous).then_some((prev, now))
 }
@@ -0,0 +1,203 @@
+use crate::{
+    editor_state::{EditorState, PendingAction},
+    loader::{self, RtmPackage, SaveOptions},
+};
+use anyhow::{Context, Result};
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy::tasks::{block_on, poll_once, IoTaskPool, Task};
+use bevy::window::WindowCloseRequested;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How often unsaved work is snapshotted to the recovery directory.
+pub const AUTOSAVE_INTERVAL_S: f64 = 30.0;
+
+/// Written next to each snapshot `.rtm`, after it, so an info file always points at a
+/// complete snapshot.
+#[derive(Serialize, Deserialize)]
+struct RecoveryInfo {
+    source: PathBuf,
+    selected_difficulty: usize,
+    saved_at: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct RecoverySnapshot {
+    /// The `.rtm` the snapshot was taken from.
+    pub source: PathBuf,
+    pub selected_difficulty: usize,
+    /// Unix time in seconds.
+    pub saved_at: u64,
+    rtm_path: PathBuf,
+    info_path: PathBuf,
+}
+
+impl RecoverySnapshot {
+    pub fn load(&self) -> Result<RtmPackage> {
+        loader::load_rtm(&self.rtm_path)
+    }
+
+    pub fn age_s(&self) -> u64 {
+        unix_now().saturating_sub(self.saved_at)
+    }
+}
+
+fn recovery_dir() -> PathBuf {
+    PathBuf::from("target").join("rtm_recovery")
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Snapshot file paths for a source `.rtm`. The path hash keeps same-named maps from
+/// different folders apart.
+fn snapshot_paths(source: &Path) -> (PathBuf, PathBuf) {
+    let stem = source
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("map");
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    let name = format!("{}-{:016x}", stem, hasher.finish());
+
+    let dir = recovery_dir();
+    (
+        dir.join(format!("{}.rtm", name)),
+        dir.join(format!("{}.json", name)),
+    )
+}
+
+pub fn write_snapshot(pkg: &RtmPackage, source: &Path, selected_difficulty: usize) -> Result<()> {
+    fs::create_dir_all(recovery_dir()).context("create recovery dir")?;
+    let (rtm_path, info_path) = snapshot_paths(source);
+
+    // Snapshots are overwritten every interval, so backups of them would only be noise.
+    loader::save_rtm_with(&rtm_path, pkg, &SaveOptions { backups: 0 })?;
+    let info = RecoveryInfo {
+        source: source.to_path_buf(),
+        selected_difficulty,
+        saved_at: unix_now(),
+    };
+    let json = serde_json::to_vec_pretty(&info).context("serialize recovery info")?;
+    fs::write(&info_path, json).with_context(|| format!("write {}", info_path.display()))?;
+    Ok(())
+}
+
+/// The most recent snapshot in the recovery directory, if any.
+pub fn latest_snapshot() -> Option<RecoverySnapshot> {
+    fs::read_dir(recovery_dir())
+        .ok()?
+        .filter_map(|entry| {
+            let info_path = entry.ok()?.path();
+            if info_path.extension().and_then(|e| e.to_str()) != Some("json") {
+                return None;
+            }
+            let rtm_path = info_path.with_extension("rtm");
+            if !rtm_path.is_file() {
+                return None;
+            }
+            let info: RecoveryInfo = serde_json::from_slice(&fs::read(&info_path).ok()?).ok()?;
+            Some(RecoverySnapshot {
+                source: info.source,
+                selected_difficulty: info.selected_difficulty,
+                saved_at: info.saved_at,
+                rtm_path,
+                info_path,
+            })
+        })
+        .max_by_key(|s| s.saved_at)
+}
+
+pub fn discard(snapshot: &RecoverySnapshot) {
+    let _ = fs::remove_file(&snapshot.info_path);
+    let _ = fs::remove_file(&snapshot.rtm_path);
+}
+
+/// Removes the snapshot for `source`, e.g. once it has been saved for real.
+pub fn discard_for(source: &Path) {
+    let (rtm_path, info_path) = snapshot_paths(source);
+    let _ = fs::remove_file(info_path);
+    let _ = fs::remove_file(rtm_path);
+}
+
+pub fn check_recovery(mut state: ResMut<EditorState>) {
+    state.recovery = latest_snapshot();
+}
+
+/// The snapshot being written in the background, and the map it belongs to.
+#[derive(Resource, Default)]
+pub struct Autosaver {
+    task: Option<Task<(PathBuf, Result<()>)>>,
+}
+
+/// Writes a snapshot of unsaved work every `AUTOSAVE_INTERVAL_S` off the UI thread, one at a time.
+pub fn autosave_system(time: Res<Time>, mut state: ResMut<EditorState>, mut autosaver: ResMut<Autosaver>) {
+    if let Some(task) = autosaver.task.as_mut() {
+        let Some((source, result)) = block_on(poll_once(task)) else {
+            return;
+        };
+        autosaver.task = None;
+        match result {
+            Ok(()) => {
+                debug!("Autosaved recovery snapshot for {}", source.display());
+                // Saved for real while the snapshot was being written.
+                if !state.dirty && state.rtm_file_path.as_deref() == Some(source.as_path()) {
+                    discard_for(&source);
+                }
+            }
+            Err(err) => {
+                state.autosave_pending = true;
+                state.status = format!("Autosave failed: {:#}", err);
+            }
+        }
+    }
+
+    let now_s = time.elapsed_seconds_f64();
+    if !state.autosave_pending || now_s - state.last_autosave_s < AUTOSAVE_INTERVAL_S {
+        return;
+    }
+    // Don't snapshot halfway through a note drag.
+    if state.timeline_note_drag.is_some() {
+        return;
+    }
+    state.last_autosave_s = now_s;
+
+    let (Some(pkg), Some(source)) = (state.package_snapshot(), state.rtm_file_path.clone()) else {
+        return;
+    };
+    // Edits made while this writes set it again.
+    state.autosave_pending = false;
+    let selected_difficulty = state.selected_difficulty;
+    autosaver.task = Some(IoTaskPool::get().spawn(async move {
+        let result = write_snapshot(&pkg, &source, selected_difficulty);
+        (source, result)
+    }));
+}
+
+/// Closing the window asks first when there are unsaved changes. The app exits once
+/// `exit_requested` is set, either here or from the unsaved-changes prompt.
+pub fn close_requested_system(
+    mut close_events: EventReader<WindowCloseRequested>,
+    mut state: ResMut<EditorState>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for _ in close_events.read() {
+        if state.dirty {
+            state.pending_action = Some(PendingAction::Quit);
+        } else {
+            state.exit_requested = true;
+        }
+    }
+    if state.exit_requested {
+        exit.send(AppExit);
+    }
+}
@@ -0,0 +1,270 @@
+use crate::{
+    audio_util,
+    editor_state::EditorState,
+    history::{BeatmapInfo, DifficultySnapshot, Edit, FileChange},
+    loader,
+    meta_ui::{has_extension, IMAGE_EXTENSIONS},
+};
+use bevy::asset::LoadState;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use std::path::PathBuf;
+
+/// The background image being shown, keyed by the `.rtm` and entry it came from and the
+/// `EditorState::package_files_revision` it was extracted at.
+#[derive(Resource, Default)]
+pub struct BackgroundLoader {
+    key: Option<(PathBuf, String, u64)>,
+    handle: Option<Handle<Image>>,
+    failed: bool,
+}
+
+/// Package entry of the working difficulty's background; the first of the mapset's
+/// background files if the difficulty doesn't name one.
+fn background_entry(state: &EditorState) -> Option<(PathBuf, String, u64)> {
+    let pkg = state.rtm_package.as_ref()?;
+    let rtm_path = state.rtm_file_path.clone()?;
+    let file = if state.beatmap.bgFile.trim().is_empty() {
+        state.meta.backgroundFiles.first()?
+    } else {
+        &state.beatmap.bgFile
+    };
+    loader::find_entry_name(pkg, file).map(|entry| (rtm_path, entry, state.package_files_revision))
+}
+
+/// Loads the working difficulty's background through the asset server and registers it
+/// with egui, replacing it whenever the difficulty's background changes.
+pub fn background_system(
+    mut contexts: EguiContexts,
+    mut state: ResMut<EditorState>,
+    mut loader: ResMut<BackgroundLoader>,
+    asset_server: Res<AssetServer>,
+    images: Res<Assets<Image>>,
+) {
+    let wanted = background_entry(&state);
+    if wanted != loader.key {
+        if let Some(handle) = loader.handle.take() {
+            contexts.remove_image(&handle);
+        }
+        state.background_texture = None;
+        loader.failed = false;
+        let previous = std::mem::replace(&mut loader.key, wanted.clone());
+
+        if let (Some((rtm_path, entry, _)), Some(pkg)) = (wanted, state.rtm_package.as_ref()) {
+            match audio_util::extract_entry(pkg, &rtm_path, &entry) {
+                Ok(rel) => {
+                    loader.handle = Some(asset_server.load(rel.clone()));
+                    // The asset server caches by path, so a replaced file has to be reloaded.
+                    if previous.is_some_and(|(p, e, _)| p == rtm_path && e == entry) {
+                        asset_server.reload(rel);
+                    }
+                }
+                Err(err) => state.status = format!("Background extract failed: {}", err),
+            }
+        }
+    }
+
+    let Some(handle) = loader.handle.clone() else {
+        return;
+    };
+    if state.background_texture.is_none() {
+        if let Some(image) = images.get(&handle) {
+            let size = image.size_f32();
+            let id = contexts.add_image(handle);
+            state.background_texture = Some((id, egui::vec2(size.x, size.y)));
+        } else if !loader.failed && asset_server.load_state(&handle) == LoadState::Failed {
+            loader.failed = true;
+            if let Some((_, entry, _)) = &loader.key {
+                state.status =
+                    format!("Background '{}' can't be previewed (only PNG and JPEG are supported)", entry);
+            }
+        }
+    }
+}
+
+/// Draws the background over `rect`, cropped to fill it and dimmed by `background_dim`.
+pub fn draw_background(ui: &egui::Ui, state: &EditorState, rect: egui::Rect) {
+    if !state.show_background {
+        return;
+    }
+    let Some((texture, size)) = state.background_texture else {
+        return;
+    };
+    if size.x <= 0.0 || size.y <= 0.0 || rect.width() <= 0.0 || rect.height() <= 0.0 {
+        return;
+    }
+
+    // Crop the image to the rect's aspect ratio around its center.
+    let image_aspect = size.x / size.y;
+    let rect_aspect = rect.width() / rect.height();
+    let uv = if image_aspect > rect_aspect {
+        let w = rect_aspect / image_aspect;
+        egui::Rect::from_min_max(egui::pos2((1.0 - w) / 2.0, 0.0), egui::pos2((1.0 + w) / 2.0, 1.0))
+    } else {
+        let h = image_aspect / rect_aspect;
+        egui::Rect::from_min_max(egui::pos2(0.0, (1.0 - h) / 2.0), egui::pos2(1.0, (1.0 + h) / 2.0))
+    };
+    let brightness = ((1.0 - state.background_dim.clamp(0.0, 1.0)) * 255.0) as u8;
+    ui.painter()
+        .image(texture, rect, uv, egui::Color32::from_gray(brightness));
+}
+
+/// `file` or `<stem>-N.<ext>`, whichever isn't in the package yet.
+fn unique_entry_name(state: &EditorState, file: &str) -> String {
+    let Some(pkg) = state.rtm_package.as_ref() else {
+        return file.to_string();
+    };
+    if !pkg.other_files.contains_key(file) {
+        return file.to_string();
+    }
+    let (stem, ext) = file.rsplit_once('.').unwrap_or((file, "png"));
+    let mut n = 2;
+    loop {
+        let candidate = format!("{}-{}.{}", stem, n, ext);
+        if !pkg.other_files.contains_key(&candidate) {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+/// The edit making `entry` the background of the working difficulty, or of every
+/// difficulty, and the status to show once it is applied.
+fn background_edit(state: &mut EditorState, entry: &str, all: bool) -> (Edit, String) {
+    if all {
+        let before = DifficultySnapshot::capture(&state.document());
+        let mut after = before.clone();
+        for d in &mut after.difficulties {
+            d.beatmap.bgFile = entry.to_string();
+        }
+        let status = format!("Background of every difficulty set to {}", entry);
+        (Edit::SetDifficulties { before, after }, status)
+    } else {
+        let before = BeatmapInfo::of(&state.beatmap);
+        let after = BeatmapInfo {
+            bg_file: entry.to_string(),
+            ..before.clone()
+        };
+        let edit = Edit::SetBeatmapInfo {
+            difficulty: state.selected_difficulty,
+            before,
+            after,
+        };
+        (edit, format!("Background set to {}", entry))
+    }
+}
+
+fn set_background(state: &mut EditorState, entry: &str, all: bool) {
+    let (edit, status) = background_edit(state, entry, all);
+    state.apply_edit(edit);
+    state.status = status;
+}
+
+/// Adds an image file to the package and makes it the background, as one undo step.
+fn import_image(state: &mut EditorState, all: bool) {
+    let Some(path) = rfd::FileDialog::new()
+        .add_filter("Image", IMAGE_EXTENSIONS)
+        .pick_file()
+    else {
+        return;
+    };
+    let bytes = match std::fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            state.status = format!("Import image failed: {}", err);
+            return;
+        }
+    };
+    let file = path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("background.png")
+        .to_string();
+    let entry = unique_entry_name(state, &file);
+    let add_file = Edit::SetPackageFiles {
+        changes: vec![FileChange {
+            name: entry.clone(),
+            before: None,
+            after: Some(bytes),
+        }],
+    };
+    let (set_background, status) = background_edit(state, &entry, all);
+    state.apply_edit(Edit::Batch {
+        label: "Import background",
+        edits: vec![add_file, set_background],
+    });
+    state.status = format!("Imported {}. {}", entry, status);
+}
+
+/// Background preview settings, and picking or importing the image for the difficulties.
+pub fn background_window_system(
+    mut contexts: EguiContexts,
+    mut state: ResMut<EditorState>,
+    mut apply_to_all: Local<bool>,
+) {
+    if !state.show_background_window {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+    let mut open = true;
+
+    egui::Window::new("🖼 Background")
+        .open(&mut open)
+        .default_width(320.0)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut state.show_background, "Show behind the keyboard");
+            ui.add(egui::Slider::new(&mut state.background_dim, 0.0..=1.0).text("Dim"));
+
+            ui.separator();
+
+            let Some(pkg) = state.rtm_package.as_ref() else {
+                ui.label("Load a mapset to set its background.");
+                return;
+            };
+            let images: Vec<String> = pkg
+                .other_files
+                .keys()
+                .filter(|f| has_extension(f, IMAGE_EXTENSIONS))
+                .cloned()
+                .collect();
+            let current = state.beatmap.bgFile.clone();
+
+            ui.label(if current.is_empty() {
+                "This difficulty has no background.".to_string()
+            } else {
+                format!("This difficulty uses {}", current)
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Apply to");
+                ui.radio_value(&mut *apply_to_all, false, "This difficulty");
+                ui.radio_value(&mut *apply_to_all, true, "All difficulties");
+            });
+
+            let mut chosen: Option<String> = None;
+            for image in &images {
+                ui.horizontal(|ui| {
+                    if ui.button("Use").clicked() {
+                        chosen = Some(image.clone());
+                    }
+                    if *image == current {
+                        ui.strong(image);
+                    } else {
+                        ui.label(image);
+                    }
+                });
+            }
+            if images.is_empty() {
+                ui.weak("The package has no images yet.");
+            }
+            if let Some(image) = chosen {
+                set_background(&mut state, &image, *apply_to_all);
+            }
+
+            if ui.button("📂 Import image…").clicked() {
+                import_image(&mut state, *apply_to_all);
+            }
+        });
+
+    state.show_background_window = open;
+}
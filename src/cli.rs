@@ -0,0 +1,325 @@
+use crate::{
+    audio_util,
+    data::MetaDifficulty,
+    loader::{self, RtmDifficulty, RtmPackage, SaveOptions},
+    star_rating, tempo, validation,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use kira::sound::static_sound::{StaticSoundData, StaticSoundSettings};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "\
+Usage: rhythm_typer_editor [COMMAND]
+
+Without a command the editor window opens. Commands run headless:
+
+  list <map.rtm>                               List difficulties and note counts
+  meta <map.rtm>                               Print meta.json
+  extract <map.rtm> <difficulty> [out.json]    Write a difficulty as JSON (stdout if no file)
+  replace <map.rtm> <difficulty> <in.json> [--out <path>] [--backups <n>]
+                                               Replace a difficulty's beatmap with JSON
+  add <map.rtm> <in.json> [--name <name>] [--out <path>] [--backups <n>]
+                                               Add a difficulty from JSON
+  validate <map.rtm>                           Check the package for problems
+  stars <map.rtm> [difficulty]                 Star rating of each difficulty (or one)
+  detect-timing <map.rtm | song>               Estimate BPM and offset
+  help                                         Show this message
+
+<difficulty> is a difficulty name, its filename, or its 0-based index.
+Commands that modify a package overwrite it unless --out is given, keeping the previous
+versions as map.1.rtm.bak (newest) and up; --backups sets how many (default 3, 0 for none).";
+
+/// Runs a CLI subcommand if one was given. Returns the process exit code, or `None` when
+/// there are no arguments and the editor should start.
+pub fn run(args: Vec<String>) -> Option<i32> {
+    let (command, rest) = args.split_first()?;
+
+    let result = match command.as_str() {
+        "list" => cmd_list(rest),
+        "meta" => cmd_meta(rest),
+        "extract" => cmd_extract(rest),
+        "replace" => cmd_replace(rest),
+        "add" => cmd_add(rest),
+        "validate" => cmd_validate(rest),
+        "stars" => cmd_stars(rest),
+        "detect-timing" => cmd_detect_timing(rest),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(0)
+        }
+        other => {
+            eprintln!("error: unknown command '{}'\n\n{}", other, USAGE);
+            Ok(2)
+        }
+    };
+
+    Some(result.unwrap_or_else(|err| {
+        eprintln!("error: {:#}", err);
+        1
+    }))
+}
+
+/// Positional arguments and `--flag value` pairs.
+struct Args {
+    positional: Vec<String>,
+    flags: Vec<(String, String)>,
+}
+
+impl Args {
+    fn parse(args: &[String], known_flags: &[&str]) -> Result<Args> {
+        let mut positional = Vec::new();
+        let mut flags = Vec::new();
+        let mut it = args.iter();
+        while let Some(arg) = it.next() {
+            if let Some(name) = arg.strip_prefix("--") {
+                if !known_flags.contains(&name) {
+                    bail!("unknown option '{}'\n\n{}", arg, USAGE);
+                }
+                let value = it
+                    .next()
+                    .ok_or_else(|| anyhow!("option '{}' needs a value", arg))?;
+                flags.push((name.to_string(), value.clone()));
+            } else {
+                positional.push(arg.clone());
+            }
+        }
+        Ok(Args { positional, flags })
+    }
+
+    fn expect_positional(&self, min: usize, max: usize) -> Result<()> {
+        let n = self.positional.len();
+        if n < min || n > max {
+            bail!("wrong number of arguments\n\n{}", USAGE);
+        }
+        Ok(())
+    }
+
+    fn flag(&self, name: &str) -> Option<&str> {
+        self.flags
+            .iter()
+            .rev()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+fn load(path: &str) -> Result<RtmPackage> {
+    loader::load_rtm(Path::new(path))
+}
+
+fn find_difficulty(pkg: &RtmPackage, query: &str) -> Result<usize> {
+    if let Some(idx) = pkg
+        .difficulties
+        .iter()
+        .position(|d| d.meta.name == query || d.meta.filename == query)
+    {
+        return Ok(idx);
+    }
+    if let Ok(idx) = query.parse::<usize>() {
+        if idx < pkg.difficulties.len() {
+            return Ok(idx);
+        }
+    }
+    Err(anyhow!("no difficulty matching '{}'", query))
+}
+
+fn output_path(args: &Args, input: &str) -> PathBuf {
+    PathBuf::from(args.flag("out").unwrap_or(input))
+}
+
+/// Saves with the default backups unless `--backups` overrides them.
+fn save(args: &Args, out: &Path, pkg: &RtmPackage) -> Result<()> {
+    let Some(n) = args.flag("backups") else {
+        return loader::save_rtm(out, pkg);
+    };
+    let backups = n
+        .parse()
+        .map_err(|_| anyhow!("--backups needs a number, got '{}'", n))?;
+    loader::save_rtm_with(out, pkg, &SaveOptions { backups })
+}
+
+fn cmd_list(rest: &[String]) -> Result<i32> {
+    let args = Args::parse(rest, &[])?;
+    args.expect_positional(1, 1)?;
+    let pkg = load(&args.positional[0])?;
+
+    for (i, d) in pkg.difficulties.iter().enumerate() {
+        let holds = d
+            .beatmap
+            .notes
+            .iter()
+            .filter(|n| n.note_type == "hold")
+            .count();
+        let taps = d.beatmap.notes.len() - holds;
+        println!(
+            "{}\t{}\t{}\tOD {:.1}\t{} notes ({} taps, {} holds)",
+            i,
+            d.meta.name,
+            d.meta.filename,
+            d.beatmap.overallDifficulty,
+            d.beatmap.notes.len(),
+            taps,
+            holds
+        );
+    }
+    Ok(0)
+}
+
+fn cmd_meta(rest: &[String]) -> Result<i32> {
+    let args = Args::parse(rest, &[])?;
+    args.expect_positional(1, 1)?;
+    let pkg = load(&args.positional[0])?;
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&pkg.meta).context("serialize meta.json")?
+    );
+    Ok(0)
+}
+
+fn cmd_extract(rest: &[String]) -> Result<i32> {
+    let args = Args::parse(rest, &[])?;
+    args.expect_positional(2, 3)?;
+    let pkg = load(&args.positional[0])?;
+    let idx = find_difficulty(&pkg, &args.positional[1])?;
+    let beatmap = &pkg.difficulties[idx].beatmap;
+
+    match args.positional.get(2) {
+        Some(out) => {
+            loader::save_beatmap(Path::new(out), beatmap)
+                .with_context(|| format!("write {}", out))?;
+            eprintln!("Extracted '{}' to {}", pkg.difficulties[idx].meta.name, out);
+        }
+        None => println!(
+            "{}",
+            serde_json::to_string_pretty(beatmap).context("serialize difficulty")?
+        ),
+    }
+    Ok(0)
+}
+
+fn cmd_replace(rest: &[String]) -> Result<i32> {
+    let args = Args::parse(rest, &["out", "backups"])?;
+    args.expect_positional(3, 3)?;
+    let mut pkg = load(&args.positional[0])?;
+    let idx = find_difficulty(&pkg, &args.positional[1])?;
+    let beatmap = loader::import_difficulty_json(Path::new(&args.positional[2]))
+        .with_context(|| format!("read {}", args.positional[2]))?;
+
+    pkg.difficulties[idx].beatmap = beatmap;
+
+    let out = output_path(&args, &args.positional[0]);
+    save(&args, &out, &pkg)?;
+    eprintln!(
+        "Replaced '{}' in {}",
+        pkg.difficulties[idx].meta.name,
+        out.display()
+    );
+    Ok(0)
+}
+
+fn cmd_add(rest: &[String]) -> Result<i32> {
+    let args = Args::parse(rest, &["name", "out", "backups"])?;
+    args.expect_positional(2, 2)?;
+    let mut pkg = load(&args.positional[0])?;
+    let json_path = Path::new(&args.positional[1]);
+    let beatmap = loader::import_difficulty_json(json_path)
+        .with_context(|| format!("read {}", json_path.display()))?;
+
+    let stem = json_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("difficulty");
+    let filename = json_path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("difficulty.json");
+    let filename = loader::unique_difficulty_filename(&pkg.difficulties, &pkg.other_files, filename);
+    let name = match args.flag("name") {
+        Some(name) => name.to_string(),
+        None => loader::imported_difficulty_name(&beatmap, stem),
+    };
+
+    pkg.difficulties.push(RtmDifficulty {
+        meta: MetaDifficulty {
+            name: name.clone(),
+            filename: filename.clone(),
+        },
+        beatmap,
+    });
+
+    let out = output_path(&args, &args.positional[0]);
+    save(&args, &out, &pkg)?;
+    eprintln!("Added '{}' ({}) to {}", name, filename, out.display());
+    Ok(0)
+}
+
+fn cmd_validate(rest: &[String]) -> Result<i32> {
+    let args = Args::parse(rest, &[])?;
+    args.expect_positional(1, 1)?;
+    // load_rtm already rejects a missing meta.json or difficulty file.
+    let pkg = load(&args.positional[0])?;
+
+    // The audio isn't decoded here, so checks against the song length are skipped.
+    let diagnostics = validation::validate_package(&pkg, None);
+    for d in &diagnostics {
+        println!("{}", d);
+    }
+
+    if validation::has_errors(&diagnostics) {
+        Ok(1)
+    } else {
+        println!(
+            "OK ({} difficulties, {} warnings)",
+            pkg.difficulties.len(),
+            diagnostics.len()
+        );
+        Ok(0)
+    }
+}
+
+fn cmd_stars(rest: &[String]) -> Result<i32> {
+    let args = Args::parse(rest, &[])?;
+    args.expect_positional(1, 2)?;
+    let pkg = load(&args.positional[0])?;
+    let indices = match args.positional.get(1) {
+        Some(query) => vec![find_difficulty(&pkg, query)?],
+        None => (0..pkg.difficulties.len()).collect(),
+    };
+
+    for idx in indices {
+        let d = &pkg.difficulties[idx];
+        let rating = star_rating::star_rating(&d.beatmap.notes);
+        println!("{}\t{}\t{:.2}*", idx, d.meta.name, rating.stars);
+    }
+    Ok(0)
+}
+
+fn cmd_detect_timing(rest: &[String]) -> Result<i32> {
+    let args = Args::parse(rest, &[])?;
+    args.expect_positional(1, 1)?;
+    let input = &args.positional[0];
+
+    let bytes = if input.to_lowercase().ends_with(".rtm") {
+        let pkg = load(input)?;
+        let entry = audio_util::find_audio_entry_name(&pkg)
+            .ok_or_else(|| anyhow!("package has no audio file '{}'", pkg.meta.audioFile))?;
+        pkg.other_files[&entry].clone()
+    } else {
+        std::fs::read(input).with_context(|| format!("read {}", input))?
+    };
+    let sound = StaticSoundData::from_cursor(Cursor::new(bytes), StaticSoundSettings::default())
+        .map_err(|e| anyhow!("decode audio: {}", e))?;
+    let samples: Vec<f32> = sound.frames.iter().map(|f| (f.left + f.right) * 0.5).collect();
+
+    let candidates = tempo::detect_timing(&samples, sound.sample_rate);
+    if candidates.is_empty() {
+        println!("No clear beat found");
+        return Ok(1);
+    }
+    for c in &candidates {
+        println!("{:.2} BPM\toffset {}ms\tconfidence {:.0}%", c.bpm, c.offset, c.confidence * 100.0);
+    }
+    Ok(0)
+}
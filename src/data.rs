@@ -1,6 +1,21 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+pub const KEYBOARD_LAYOUT: &[&[&str]] = &[
+    &["Q", "W", "E", "R", "T", "Y", "U", "I", "O", "P"],
+    &["A", "S", "D", "F", "G", "H", "J", "K", "L", ";"],
+    &["Z", "X", "C", "V", "B", "N", "M", ",", ".", "/"],
+];
+
+/// (row, column) of a key in `KEYBOARD_LAYOUT`, case-insensitive.
+pub fn key_position(key: &str) -> Option<(usize, usize)> {
+    KEYBOARD_LAYOUT.iter().enumerate().find_map(|(row, keys)| {
+        keys.iter()
+            .position(|k| k.eq_ignore_ascii_case(key))
+            .map(|col| (row, col))
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BeatmapNote {
     pub key: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -14,7 +29,7 @@ pub struct BeatmapNote {
     pub hitsound: Hitsound,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Hitsound {
     pub sampleSet: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -29,7 +44,7 @@ pub struct Hitsound {
     pub end: Option<HitsoundPart>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SoundConfig {
     pub hitnormal: bool,
     pub hitclap: bool,
@@ -37,21 +52,21 @@ pub struct SoundConfig {
     pub hitfinish: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct HitsoundPart {
     pub volume: u32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sounds: Option<SoundConfig>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct HoldConfig {
     pub volume: u32,
     #[serde(rename = "loop")]
     pub loop_field: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Beatmap {
     pub name: String,
     pub overallDifficulty: f32,
@@ -59,7 +74,7 @@ pub struct Beatmap {
     pub notes: Vec<BeatmapNote>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct Meta {
     #[serde(default)]
     pub songName: String,
@@ -97,7 +112,7 @@ pub struct Meta {
     pub hasCustomHitsounds: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TimingPoint {
     pub id: f64,
     pub time: f64,
@@ -106,12 +121,18 @@ pub struct TimingPoint {
     pub timeSignature: [i64; 2],
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MetaDifficulty {
     pub name: String,
     pub filename: String,
 }
 
+impl Default for Beatmap {
+    fn default() -> Self {
+        Beatmap::new()
+    }
+}
+
 impl Beatmap {
     pub fn new() -> Self {
         Beatmap {
@@ -138,20 +159,122 @@ impl Beatmap {
         }
     }
 
-    pub fn add_tap_note(&mut self, key: String, time: u32) {
-        self.notes.push(BeatmapNote {
+    /// Inserts `note` after the notes that start at or before it, keeping the notes sorted.
+    pub fn insert_note(&mut self, note: BeatmapNote) {
+        let start = note.get_start_time();
+        let idx = self.notes.partition_point(|n| n.get_start_time() <= start);
+        self.notes.insert(idx, note);
+    }
+
+    /// Inserts several notes with a single sort.
+    pub fn insert_notes(&mut self, notes: impl IntoIterator<Item = BeatmapNote>) {
+        self.notes.extend(notes);
+        self.notes.sort_by_key(|n| n.get_start_time());
+    }
+
+    /// Index of the first note equal to `note` that `skip` doesn't rule out. Searches only
+    /// the notes with the same start time, unless the notes aren't sorted (as loaded).
+    fn find_note(&self, note: &BeatmapNote, skip: impl Fn(usize) -> bool) -> Option<usize> {
+        let start = note.get_start_time();
+        let lo = self.notes.partition_point(|n| n.get_start_time() < start);
+        let found = self.notes[lo..]
+            .iter()
+            .take_while(|n| n.get_start_time() == start)
+            .enumerate()
+            .map(|(i, n)| (lo + i, n))
+            .find(|&(i, n)| n == note && !skip(i))
+            .map(|(i, _)| i);
+        found.or_else(|| {
+            self.notes
+                .iter()
+                .enumerate()
+                .position(|(i, n)| n == note && !skip(i))
+        })
+    }
+
+    /// Removes the first note equal to `note`. Returns false if no such note exists.
+    pub fn remove_note(&mut self, note: &BeatmapNote) -> bool {
+        if let Some(idx) = self.find_note(note, |_| false) {
+            self.notes.remove(idx);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Removes one note equal to each of `notes` in a single pass, skipping those that
+    /// don't exist. Returns how many were removed.
+    pub fn remove_notes(&mut self, notes: &[BeatmapNote]) -> usize {
+        let mut removed = vec![false; self.notes.len()];
+        let mut count = 0;
+        for note in notes {
+            if let Some(idx) = self.find_note(note, |i| removed[i]) {
+                removed[idx] = true;
+                count += 1;
+            }
+        }
+        let mut idx = 0;
+        self.notes.retain(|_| {
+            idx += 1;
+            !removed[idx - 1]
+        });
+        count
+    }
+
+    pub fn find_note_at(&self, key: &str, time_ms: u32) -> Option<usize> {
+        let key_lc = key.to_lowercase();
+
+        // Prefer a hold that covers time_ms, otherwise a tap exactly at time_ms.
+        // If multiple candidates exist, pick the one with the closest start time.
+        let mut best_idx: Option<usize> = None;
+        let mut best_score: u32 = u32::MAX;
+
+        for (idx, n) in self.notes.iter().enumerate() {
+            if n.key.to_lowercase() != key_lc {
+                continue;
+            }
+
+            if n.note_type == "hold" {
+                let s = n.get_start_time();
+                let e = n.get_end_time();
+                if time_ms >= s && time_ms <= e {
+                    let score = time_ms.abs_diff(s);
+                    if score < best_score {
+                        best_score = score;
+                        best_idx = Some(idx);
+                    }
+                }
+            } else {
+                let s = n.get_start_time();
+                if s == time_ms {
+                    // Exact tap match is a strong candidate.
+                    let score = 0;
+                    if score <= best_score {
+                        best_score = score;
+                        best_idx = Some(idx);
+                    }
+                }
+            }
+        }
+
+        best_idx
+    }
+}
+
+impl BeatmapNote {
+    pub fn tap(key: String, time: u32) -> Self {
+        BeatmapNote {
             key,
             time: Some(time),
             startTime: None,
             endTime: None,
             note_type: "tap".to_string(),
-            hitsound: Self::default_hitsound(),
-        });
-        self.notes.sort_by_key(|n| n.get_start_time());
+            hitsound: Beatmap::default_hitsound(),
+        }
     }
 
-    pub fn add_hold_note(&mut self, key: String, start_time: u32, end_time: u32) {
-        self.notes.push(BeatmapNote {
+    pub fn hold(key: String, start_time: u32, end_time: u32) -> Self {
+        BeatmapNote {
             key,
             time: None,
             startTime: Some(start_time),
@@ -184,61 +307,60 @@ impl Beatmap {
                     }),
                 }),
             },
-        });
-        self.notes.sort_by_key(|n| n.get_start_time());
+        }
     }
 
-    pub fn delete_note_at(&mut self, key: &str, time_ms: u32) -> bool {
-        let key_lc = key.to_lowercase();
+    pub fn get_start_time(&self) -> u32 {
+        self.time.or(self.startTime).unwrap_or(0)
+    }
 
-        // Prefer deleting a hold that covers time_ms, otherwise delete a tap exactly at time_ms.
-        // If multiple candidates exist, delete the one with the closest start time.
-        let mut best_idx: Option<usize> = None;
-        let mut best_score: u32 = u32::MAX;
+    pub fn get_end_time(&self) -> u32 {
+        self.endTime.unwrap_or(self.get_start_time())
+    }
+}
 
-        for (idx, n) in self.notes.iter().enumerate() {
-            if n.key.to_lowercase() != key_lc {
-                continue;
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            if n.note_type == "hold" {
-                let s = n.get_start_time();
-                let e = n.get_end_time();
-                if time_ms >= s && time_ms <= e {
-                    let score = time_ms.abs_diff(s);
-                    if score < best_score {
-                        best_score = score;
-                        best_idx = Some(idx);
-                    }
-                }
-            } else {
-                let s = n.get_start_time();
-                if s == time_ms {
-                    // Exact tap match is a strong candidate.
-                    let score = 0;
-                    if score <= best_score {
-                        best_score = score;
-                        best_idx = Some(idx);
-                    }
-                }
-            }
-        }
+    fn tap(key: &str, time: u32) -> BeatmapNote {
+        BeatmapNote::tap(key.to_string(), time)
+    }
 
-        if let Some(idx) = best_idx {
-            self.notes.remove(idx);
-            true
-        } else {
-            false
+    fn beatmap(notes: &[BeatmapNote]) -> Beatmap {
+        Beatmap {
+            notes: notes.to_vec(),
+            ..Beatmap::new()
         }
     }
-}
 
-impl BeatmapNote {
-    pub fn get_start_time(&self) -> u32 {
-        self.time.or(self.startTime).unwrap_or(0)
+    #[test]
+    fn insert_keeps_notes_sorted_and_after_equal_starts() {
+        let mut map = beatmap(&[tap("a", 100), tap("b", 200), tap("c", 300)]);
+        map.insert_note(tap("d", 200));
+        map.insert_note(tap("e", 0));
+        let keys: Vec<&str> = map.notes.iter().map(|n| n.key.as_str()).collect();
+        assert_eq!(keys, ["e", "a", "b", "d", "c"]);
+
+        map.insert_notes([tap("f", 250), tap("g", 100)]);
+        let keys: Vec<&str> = map.notes.iter().map(|n| n.key.as_str()).collect();
+        assert_eq!(keys, ["e", "a", "g", "b", "d", "f", "c"]);
     }
 
-    pub fn get_end_time(&self) -> u32 {
-        self.endTime.unwrap_or(self.get_start_time())
+    #[test]
+    fn remove_notes_removes_one_match_each() {
+        let mut map = beatmap(&[tap("a", 100), tap("a", 100), tap("b", 200), tap("a", 100)]);
+        let removed = map.remove_notes(&[tap("a", 100), tap("a", 100), tap("z", 100)]);
+        assert_eq!(removed, 2);
+        assert_eq!(map.notes, vec![tap("b", 200), tap("a", 100)]);
+    }
+
+    #[test]
+    fn remove_finds_notes_in_an_unsorted_beatmap() {
+        let mut map = beatmap(&[tap("c", 300), tap("a", 100), tap("b", 200)]);
+        assert!(map.remove_note(&tap("a", 100)));
+        assert_eq!(map.remove_notes(&[tap("c", 300), tap("b", 200)]), 2);
+        assert!(map.notes.is_empty());
+        assert!(!map.remove_note(&tap("a", 100)));
     }
 }
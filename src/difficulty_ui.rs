@@ -0,0 +1,231 @@
+use crate::{
+    data::{Beatmap, MetaDifficulty},
+    editor_state::EditorState,
+    history::{DifficultySnapshot, Edit},
+    loader::{self, RtmDifficulty},
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+enum DifficultyAction {
+    New,
+    Duplicate,
+    Rename(String),
+    /// Deletes the difficulty saved as this file name.
+    Delete(String),
+    Move { from: usize, to: usize },
+}
+
+/// Applies `action` to the difficulty list as one undoable edit.
+fn apply_action(state: &mut EditorState, action: DifficultyAction) {
+    let before = DifficultySnapshot::capture(&state.document());
+    let mut after = before.clone();
+    let sel = before.selected;
+    // Difficulty files can't take the names of meta.json or the package's other files.
+    let Some(other_files) = state.rtm_package.as_ref().map(|p| &p.other_files) else {
+        return;
+    };
+    // Whether the working difficulty becomes a different one.
+    let mut switched = true;
+
+    let status = match action {
+        DifficultyAction::New => {
+            let name = "New Difficulty".to_string();
+            let filename = loader::unique_difficulty_filename(
+                &after.difficulties,
+                other_files,
+                &loader::difficulty_filename(&name),
+            );
+            let beatmap = Beatmap {
+                name: name.clone(),
+                // Difficulties of a mapset usually share the background.
+                bgFile: state.beatmap.bgFile.clone(),
+                ..Beatmap::new()
+            };
+            after.difficulties.push(RtmDifficulty {
+                meta: MetaDifficulty { name: name.clone(), filename },
+                beatmap,
+            });
+            after.selected = after.difficulties.len() - 1;
+            format!("Added difficulty: {}", name)
+        }
+        DifficultyAction::Duplicate => {
+            let Some(mut copy) = after.difficulties.get(sel).cloned() else {
+                return;
+            };
+            let name = format!("{} (copy)", copy.meta.name);
+            copy.meta.filename = loader::unique_difficulty_filename(
+                &after.difficulties,
+                other_files,
+                &loader::difficulty_filename(&name),
+            );
+            copy.meta.name = name.clone();
+            copy.beatmap.name = name.clone();
+            after.difficulties.insert(sel + 1, copy);
+            after.selected = sel + 1;
+            format!("Duplicated difficulty: {}", name)
+        }
+        DifficultyAction::Rename(name) => {
+            loader::rename_difficulty(&mut after.difficulties, other_files, sel, &name);
+            switched = false;
+            format!("Renamed difficulty to {}", name)
+        }
+        DifficultyAction::Delete(filename) => {
+            let Some(idx) = after.difficulties.iter().position(|d| d.meta.filename == filename) else {
+                return;
+            };
+            if after.difficulties.len() <= 1 {
+                return;
+            }
+            let removed = after.difficulties.remove(idx);
+            switched = idx == sel;
+            if sel > idx || sel >= after.difficulties.len() {
+                after.selected = sel.saturating_sub(1);
+            }
+            format!("Deleted difficulty: {}", removed.meta.name)
+        }
+        DifficultyAction::Move { from, to } => {
+            if from >= after.difficulties.len() || to >= after.difficulties.len() {
+                return;
+            }
+            let d = after.difficulties.remove(from);
+            after.difficulties.insert(to, d);
+            switched = false;
+            // Keep the working difficulty selected wherever it ends up.
+            after.selected = if sel == from {
+                to
+            } else if from < sel && sel <= to {
+                sel - 1
+            } else if to <= sel && sel < from {
+                sel + 1
+            } else {
+                sel
+            };
+            "Reordered difficulties".to_string()
+        }
+    };
+
+    // Restoring the snapshot also keeps meta.difficulties in sync
+    state.apply_edit(Edit::SetDifficulties { before, after });
+    if switched {
+        state.hold_starts.clear();
+        state.selection.clear();
+    }
+    state.status = status;
+}
+
+/// Window for adding, duplicating, renaming, deleting and reordering difficulties.
+pub fn difficulty_window_system(
+    mut contexts: EguiContexts,
+    mut state: ResMut<EditorState>,
+    // (difficulty, its name when editing started, edited name)
+    mut rename: Local<Option<(usize, String, String)>>,
+    // File name of the difficulty waiting for the user to confirm deleting it. Looked up
+    // again on confirming, as the list may have changed since (e.g. by undo).
+    mut confirm_delete: Local<Option<String>>,
+) {
+    if !state.show_difficulties {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+    let mut open = true;
+    let mut action: Option<DifficultyAction> = None;
+
+    let Some(pkg) = state.rtm_package.as_ref() else {
+        state.show_difficulties = false;
+        return;
+    };
+    let entries: Vec<MetaDifficulty> = pkg.difficulties.iter().map(|d| d.meta.clone()).collect();
+    let selected = state.selected_difficulty;
+    let current_name = entries.get(selected).map(|d| d.name.clone()).unwrap_or_default();
+    if !matches!(rename.as_ref(), Some((idx, orig, _)) if *idx == selected && *orig == current_name) {
+        *rename = Some((selected, current_name.clone(), current_name.clone()));
+    }
+
+    egui::Window::new("🗂 Difficulties")
+        .open(&mut open)
+        .default_width(360.0)
+        .show(ctx, |ui| {
+            let count = entries.len();
+            egui::Grid::new("difficulty_list").num_columns(3).striped(true).show(ui, |ui| {
+                for (i, d) in entries.iter().enumerate() {
+                    if ui.selectable_label(i == selected, &d.name).clicked() && i != selected {
+                        state.select_difficulty(i);
+                        state.status = "Switched difficulty".to_string();
+                    }
+                    ui.weak(&d.filename);
+                    ui.horizontal(|ui| {
+                        if ui.add_enabled(i > 0, egui::Button::new("⬆")).clicked() {
+                            action = Some(DifficultyAction::Move { from: i, to: i - 1 });
+                        }
+                        if ui.add_enabled(i + 1 < count, egui::Button::new("⬇")).clicked() {
+                            action = Some(DifficultyAction::Move { from: i, to: i + 1 });
+                        }
+                        if ui
+                            .add_enabled(count > 1, egui::Button::new("🗑"))
+                            .on_hover_text("Delete this difficulty")
+                            .clicked()
+                        {
+                            *confirm_delete = Some(d.filename.clone());
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                if ui.button("➕ New").on_hover_text("Add an empty difficulty").clicked() {
+                    action = Some(DifficultyAction::New);
+                }
+                if ui.button("⎘ Duplicate").on_hover_text("Copy the current difficulty").clicked() {
+                    action = Some(DifficultyAction::Duplicate);
+                }
+            });
+
+            if let Some((_, orig, name)) = rename.as_mut() {
+                ui.horizontal(|ui| {
+                    let resp = ui.text_edit_singleline(name);
+                    let valid = !name.trim().is_empty() && name.trim() != orig.as_str();
+                    let submitted = resp.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if (ui.add_enabled(valid, egui::Button::new("Rename")).clicked() || submitted) && valid {
+                        action = Some(DifficultyAction::Rename(name.trim().to_string()));
+                    }
+                });
+                ui.weak("Renaming also renames the difficulty's file in the .rtm.");
+            }
+        });
+
+    let pending_name = confirm_delete
+        .as_ref()
+        .and_then(|f| entries.iter().find(|d| d.filename == *f))
+        .map(|d| d.name.clone());
+    if pending_name.is_none() {
+        // Nothing to confirm, or the difficulty is gone already.
+        *confirm_delete = None;
+    }
+    if let Some(name) = pending_name {
+        egui::Window::new("Delete difficulty")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(format!("Delete '{}' and all of its notes?", name));
+                ui.label("You can bring it back with Undo.");
+                ui.horizontal(|ui| {
+                    if ui.button("🗑 Delete").clicked() {
+                        action = confirm_delete.take().map(DifficultyAction::Delete);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        *confirm_delete = None;
+                    }
+                });
+            });
+    }
+
+    if let Some(action) = action {
+        apply_action(&mut state, action);
+    }
+    state.show_difficulties = open;
+}
@@ -1,17 +1,47 @@
 use bevy::prelude::*;
+use bevy_egui::egui;
 
-use crate::{data::{Beatmap, Meta}, loader::RtmPackage};
+use crate::{
+    autosave::RecoverySnapshot,
+    data::{Beatmap, BeatmapNote, Meta},
+    history::{Document, Edit, History},
+    judgement::Judge,
+    hitsound_ui::HitsoundTarget,
+    loader::{RtmPackage, SaveOptions},
+    record::{self, RecordTake},
+    selection::{self, NoteDrag, Selection},
+    star_rating::StarRating,
+    tempo::TimingCandidate,
+    validation::Diagnostic,
+    waveform::Waveform,
+};
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 pub const AUDIO_POLL_INTERVAL_S: f32 = 0.5;
 
+/// Preview speeds offered in the top bar.
+pub const PLAYBACK_RATES: &[f64] = &[0.25, 0.5, 0.75, 1.0, 1.5];
+
+/// An action that would throw away unsaved changes, held until the user confirms it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PendingAction {
+    Load,
+    NewMapset,
+    Quit,
+}
+
 #[derive(Resource)]
 pub struct EditorState {
     pub beatmap: Beatmap,
     pub meta: Meta,
     pub rtm_package: Option<RtmPackage>,
     pub rtm_file_path: Option<PathBuf>,
+    /// Bumped whenever the package's other files may have changed, so whatever was extracted
+    /// from them knows to refresh.
+    pub package_files_revision: u64,
+    pub save_options: SaveOptions,
     pub selected_difficulty: usize,
 
     pub audio_rel_path: Option<String>,
@@ -19,19 +49,88 @@ pub struct EditorState {
     pub audio_seek_request: Option<u32>,
     pub audio_instance: Option<Handle<bevy_kira_audio::AudioInstance>>,
     pub audio_poll_accum_s: f32,
+    pub waveform: Option<Arc<Waveform>>,
+    pub timing_detect_requested: bool,
+    pub timing_detect_running: bool,
+    pub timing_candidates: Vec<TimingCandidate>,
+    /// Onset times (ms) detected in the song, unsnapped. Only shown as suggestions.
+    pub onsets: Option<Arc<Vec<u32>>>,
+    pub show_suggestions: bool,
+    pub suggestion_range_ms: (u32, u32),
+    pub suggestion_key: String,
+    pub record_mode: bool,
+    pub record_quantize: bool,
+    /// Subtracted from the playhead time of each key press, when recording and playtesting.
+    pub input_latency_ms: i32,
+    /// Keys held at least this long while recording become hold notes.
+    pub record_hold_ms: u32,
+    pub record_take: Option<RecordTake>,
+    pub playtest: Option<Judge>,
+    /// The notes last rated and their rating.
+    pub star_rating_cache: Option<(Vec<BeatmapNote>, StarRating)>,
+    pub playback_rate: f64,
+    /// The rate last sent to `audio_instance`.
+    pub audio_applied_rate: f64,
+    /// Sub-millisecond remainder of playback time, so slow rates don't lose time to rounding.
+    pub playback_frac_ms: f64,
+    /// Set on frames where the playhead was moved by a seek rather than by playback.
+    pub playhead_jumped: bool,
 
     pub current_time: u32,
     pub is_hold_mode: bool,
     pub hold_starts: HashMap<String, u32>,
     pub is_playing: bool,
+    pub beat_divisor: u32,
+    pub hitsounds_enabled: bool,
+    pub metronome_enabled: bool,
+    pub metronome_volume: f32,
 
     pub timeline_window_ms: f32,
     pub timeline_playhead_ratio: f32,
     pub timeline_row_spacing: f32,
     pub timeline_drag_last_dx: Option<f32>,
+    pub timeline_box_anchor: Option<(f32, f32)>,
+    pub timeline_note_drag: Option<NoteDrag>,
 
     pub hovered_key: Option<String>,
 
+    pub selection: Selection,
+    pub clipboard: Vec<BeatmapNote>,
+
+    pub hitsound_target: HitsoundTarget,
+    pub hitsound_range_ms: (u32, u32),
+    pub hitsound_keys: String,
+
+    pub history: History,
+
+    pub diagnostics: Vec<Diagnostic>,
+    pub show_validation: bool,
+    pub validation_blocked_save: bool,
+
+    pub show_samples: bool,
+    pub show_stats: bool,
+    pub show_difficulties: bool,
+    pub show_background_window: bool,
+    /// Draw the difficulty's background image behind the keyboard.
+    pub show_background: bool,
+    /// 0 shows the background as is, 1 hides it completely.
+    pub background_dim: f32,
+    /// The loaded background, registered with egui, and its size in pixels.
+    pub background_texture: Option<(egui::TextureId, egui::Vec2)>,
+    /// Text typed into the meta editor's tag entry.
+    pub meta_new_tag: String,
+    /// Tints the on-screen keyboard by how often each key is used.
+    pub show_key_heatmap: bool,
+
+    /// True when there are edits that haven't been written to the `.rtm`.
+    pub dirty: bool,
+    /// True when there are edits that haven't been autosaved yet.
+    pub autosave_pending: bool,
+    pub last_autosave_s: f64,
+    pub recovery: Option<RecoverySnapshot>,
+    pub pending_action: Option<PendingAction>,
+    pub exit_requested: bool,
+
     pub status: String,
 }
 
@@ -42,6 +141,8 @@ impl Default for EditorState {
             meta: Meta::default(),
             rtm_package: None,
             rtm_file_path: None,
+            package_files_revision: 0,
+            save_options: SaveOptions::default(),
             selected_difficulty: 0,
 
             audio_rel_path: None,
@@ -49,20 +150,237 @@ impl Default for EditorState {
             audio_seek_request: None,
             audio_instance: None,
             audio_poll_accum_s: 0.0,
+            waveform: None,
+            timing_detect_requested: false,
+            timing_detect_running: false,
+            timing_candidates: Vec::new(),
+            onsets: None,
+            show_suggestions: false,
+            suggestion_range_ms: (0, 0),
+            suggestion_key: "F".to_string(),
+            record_mode: false,
+            record_quantize: true,
+            input_latency_ms: 0,
+            record_hold_ms: 200,
+            record_take: None,
+            playtest: None,
+            star_rating_cache: None,
+            playback_rate: 1.0,
+            audio_applied_rate: 1.0,
+            playback_frac_ms: 0.0,
+            playhead_jumped: false,
 
             current_time: 0,
             is_hold_mode: false,
             hold_starts: HashMap::new(),
             is_playing: false,
+            beat_divisor: 2,
+            hitsounds_enabled: true,
+            metronome_enabled: false,
+            metronome_volume: 0.6,
 
             timeline_window_ms: 10_000.0,
             timeline_playhead_ratio: 0.4,
             timeline_row_spacing: 30.0,
             timeline_drag_last_dx: None,
+            timeline_box_anchor: None,
+            timeline_note_drag: None,
 
             hovered_key: None,
 
+            selection: Selection::default(),
+            clipboard: Vec::new(),
+
+            hitsound_target: HitsoundTarget::default(),
+            hitsound_range_ms: (0, 0),
+            hitsound_keys: String::new(),
+
+            history: History::default(),
+
+            diagnostics: Vec::new(),
+            show_validation: false,
+            validation_blocked_save: false,
+
+            show_samples: false,
+            show_stats: false,
+            show_difficulties: false,
+            show_background_window: false,
+            show_background: true,
+            background_dim: 0.7,
+            background_texture: None,
+            meta_new_tag: String::new(),
+            show_key_heatmap: false,
+
+            dirty: false,
+            autosave_pending: false,
+            last_autosave_s: 0.0,
+            recovery: None,
+            pending_action: None,
+            exit_requested: false,
+
             status: "Ready".to_string(),
         }
     }
 }
+
+impl EditorState {
+    /// Replaces the open mapset with `pkg` and resets playback/editing state.
+    pub fn load_package(&mut self, pkg: RtmPackage, path: PathBuf) {
+        self.meta = pkg.meta.clone();
+        self.beatmap = pkg
+            .difficulties
+            .first()
+            .map(|d| d.beatmap.clone())
+            .unwrap_or_default();
+        self.rtm_package = Some(pkg);
+        self.rtm_file_path = Some(path);
+        self.package_files_revision += 1;
+        self.selected_difficulty = 0;
+
+        self.current_time = 0;
+        self.is_playing = false;
+        self.is_hold_mode = false;
+        self.hold_starts.clear();
+        self.audio_rel_path = None;
+        self.audio_handle = None;
+        self.audio_seek_request = Some(0);
+        self.audio_instance = None;
+        self.timing_candidates.clear();
+        self.history.clear();
+        self.selection.clear();
+        self.timeline_note_drag = None;
+        self.record_take = None;
+        self.playtest = None;
+        self.diagnostics.clear();
+        self.validation_blocked_save = false;
+        self.dirty = false;
+        self.autosave_pending = false;
+    }
+
+    /// Writes the working beatmap and meta back into the package.
+    pub fn sync_package(&mut self) {
+        let idx = self.selected_difficulty;
+        if let Some(pkg) = self.rtm_package.as_mut() {
+            if let Some(cur) = pkg.difficulties.get_mut(idx) {
+                cur.beatmap = self.beatmap.clone();
+            }
+            pkg.meta = self.meta.clone();
+        }
+    }
+
+    /// A copy of the package with the working beatmap and meta written into it.
+    pub fn package_snapshot(&self) -> Option<RtmPackage> {
+        let mut pkg = self.rtm_package.clone()?;
+        if let Some(cur) = pkg.difficulties.get_mut(self.selected_difficulty) {
+            cur.beatmap = self.beatmap.clone();
+        }
+        pkg.meta = self.meta.clone();
+        Some(pkg)
+    }
+
+    /// Makes `idx` the working difficulty, keeping edits made to the current one.
+    pub fn select_difficulty(&mut self, idx: usize) {
+        if idx == self.selected_difficulty {
+            return;
+        }
+        // A take belongs to the difficulty it was recorded on.
+        record::finish_take(self);
+        let Some(pkg) = self.rtm_package.as_mut() else {
+            return;
+        };
+        let Some(next) = pkg.difficulties.get(idx).map(|d| d.beatmap.clone()) else {
+            return;
+        };
+        if let Some(cur) = pkg.difficulties.get_mut(self.selected_difficulty) {
+            cur.beatmap = std::mem::replace(&mut self.beatmap, next);
+        } else {
+            self.beatmap = next;
+        }
+        self.selected_difficulty = idx;
+        self.hold_starts.clear();
+        self.selection.clear();
+        self.timeline_note_drag = None;
+    }
+
+    pub fn document(&mut self) -> Document<'_> {
+        self.history_and_document().1
+    }
+
+    /// Borrows the history alongside the document it edits.
+    fn history_and_document(&mut self) -> (&mut History, Document<'_>) {
+        let doc = Document {
+            beatmap: &mut self.beatmap,
+            meta: &mut self.meta,
+            package: self.rtm_package.as_mut(),
+            selected_difficulty: &mut self.selected_difficulty,
+        };
+        (&mut self.history, doc)
+    }
+
+    fn with_history<R>(&mut self, f: impl FnOnce(&mut History, &mut Document) -> R) -> R {
+        let (history, mut doc) = self.history_and_document();
+        f(history, &mut doc)
+    }
+
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+        self.autosave_pending = true;
+    }
+
+    /// Applies an edit to the open mapset and records it for undo.
+    pub fn apply_edit(&mut self, edit: Edit) {
+        // Keep a take in progress ahead of this edit in the history.
+        record::finish_take(self);
+        if edit.touches_package_files() {
+            self.package_files_revision += 1;
+        }
+        self.with_history(|history, doc| history.execute(edit, doc));
+        self.mark_dirty();
+    }
+
+    /// Like [`EditorState::apply_edit`], but folds quick successive edits of the same notes
+    /// into one undo step.
+    pub fn apply_edit_merging(&mut self, edit: Edit, now_s: f64) {
+        record::finish_take(self);
+        if edit.touches_package_files() {
+            self.package_files_revision += 1;
+        }
+        self.with_history(|history, doc| history.execute_merging(edit, doc, now_s));
+        self.mark_dirty();
+    }
+
+    pub fn undo(&mut self) {
+        // Commit a drag that is still in progress so the beatmap and history agree.
+        selection::finish_note_drag(self);
+        record::finish_take(self);
+        if self.history.next_undo().is_some_and(Edit::touches_package_files) {
+            self.package_files_revision += 1;
+        }
+        let label = self.with_history(|history, doc| history.undo(doc));
+        self.hold_starts.clear();
+        if label.is_some() {
+            self.mark_dirty();
+        }
+        self.status = match label {
+            Some(label) => format!("Undo: {}", label),
+            None => "Nothing to undo".to_string(),
+        };
+    }
+
+    pub fn redo(&mut self) {
+        selection::finish_note_drag(self);
+        record::finish_take(self);
+        if self.history.next_redo().is_some_and(Edit::touches_package_files) {
+            self.package_files_revision += 1;
+        }
+        let label = self.with_history(|history, doc| history.redo(doc));
+        self.hold_starts.clear();
+        if label.is_some() {
+            self.mark_dirty();
+        }
+        self.status = match label {
+            Some(label) => format!("Redo: {}", label),
+            None => "Nothing to redo".to_string(),
+        };
+    }
+}
@@ -0,0 +1,769 @@
+use crate::{
+    data::{Beatmap, BeatmapNote, Meta},
+    hitsound_util::has_custom_samples,
+    loader::{RtmDifficulty, RtmPackage},
+};
+
+pub const MAX_HISTORY: usize = 500;
+
+// Consecutive edits of the same kind within this window collapse into one undo step,
+// so typing into a text field or dragging a slider doesn't create an entry per frame.
+pub const MERGE_WINDOW_S: f64 = 1.0;
+
+/// Everything an [`Edit`] can touch. Borrowed from `EditorState` in the editor, but can be
+/// assembled from plain values so history can be driven without any UI.
+pub struct Document<'a> {
+    pub beatmap: &'a mut Beatmap,
+    pub meta: &'a mut Meta,
+    pub package: Option<&'a mut RtmPackage>,
+    pub selected_difficulty: &'a mut usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeatmapInfo {
+    pub name: String,
+    pub overall_difficulty: f32,
+    pub bg_file: String,
+}
+
+impl BeatmapInfo {
+    pub fn of(beatmap: &Beatmap) -> Self {
+        BeatmapInfo {
+            name: beatmap.name.clone(),
+            overall_difficulty: beatmap.overallDifficulty,
+            bg_file: beatmap.bgFile.clone(),
+        }
+    }
+
+    fn apply_to(&self, beatmap: &mut Beatmap) {
+        beatmap.name = self.name.clone();
+        beatmap.overallDifficulty = self.overall_difficulty;
+        beatmap.bgFile = self.bg_file.clone();
+    }
+}
+
+/// The package's difficulty list, with the working beatmap flushed into the selected entry.
+#[derive(Debug, Clone)]
+pub struct DifficultySnapshot {
+    pub difficulties: Vec<RtmDifficulty>,
+    pub selected: usize,
+}
+
+impl DifficultySnapshot {
+    pub fn capture(doc: &Document) -> Self {
+        let mut difficulties = doc
+            .package
+            .as_ref()
+            .map(|p| p.difficulties.clone())
+            .unwrap_or_default();
+        let selected = *doc.selected_difficulty;
+        if let Some(cur) = difficulties.get_mut(selected) {
+            cur.beatmap = doc.beatmap.clone();
+        }
+        DifficultySnapshot {
+            difficulties,
+            selected,
+        }
+    }
+
+    fn restore(&self, doc: &mut Document) {
+        let Some(pkg) = doc.package.as_mut() else {
+            return;
+        };
+        pkg.difficulties = self.difficulties.clone();
+        *doc.selected_difficulty = self.selected.min(pkg.difficulties.len().saturating_sub(1));
+        if let Some(sel) = pkg.difficulties.get(*doc.selected_difficulty) {
+            *doc.beatmap = sel.beatmap.clone();
+        }
+        doc.meta.difficulties = pkg.difficulties.iter().map(|d| d.meta.clone()).collect();
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Edit {
+    AddNotes {
+        difficulty: usize,
+        notes: Vec<BeatmapNote>,
+    },
+    RemoveNotes {
+        difficulty: usize,
+        notes: Vec<BeatmapNote>,
+    },
+    /// Covers moves and any other in-place change to existing notes.
+    ReplaceNotes {
+        difficulty: usize,
+        before: Vec<BeatmapNote>,
+        after: Vec<BeatmapNote>,
+    },
+    SetBeatmapInfo {
+        difficulty: usize,
+        before: BeatmapInfo,
+        after: BeatmapInfo,
+    },
+    /// Metadata and timing point edits. `Meta::difficulties` is owned by the difficulty
+    /// list, so it is left alone when this is applied.
+    SetMeta {
+        before: Box<Meta>,
+        after: Box<Meta>,
+    },
+    SetDifficulties {
+        before: DifficultySnapshot,
+        after: DifficultySnapshot,
+    },
+    /// Adds, replaces or removes files in `RtmPackage::other_files`. `None` means absent.
+    /// `Meta::hasCustomHitsounds` follows whether any custom samples are left.
+    SetPackageFiles {
+        changes: Vec<FileChange>,
+    },
+    /// Several edits applied in order and undone as one step.
+    Batch {
+        label: &'static str,
+        edits: Vec<Edit>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct FileChange {
+    pub name: String,
+    pub before: Option<Vec<u8>>,
+    pub after: Option<Vec<u8>>,
+}
+
+impl Edit {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Edit::AddNotes { notes, .. } if notes.len() == 1 => "Add note",
+            Edit::AddNotes { .. } => "Add notes",
+            Edit::RemoveNotes { notes, .. } if notes.len() == 1 => "Delete note",
+            Edit::RemoveNotes { .. } => "Delete notes",
+            Edit::ReplaceNotes { .. } => "Edit notes",
+            Edit::SetBeatmapInfo { .. } => "Beatmap settings",
+            Edit::SetMeta { .. } => "Meta",
+            Edit::SetDifficulties { .. } => "Difficulties",
+            Edit::SetPackageFiles { .. } => "Package files",
+            Edit::Batch { label, .. } => label,
+        }
+    }
+
+    fn inverse(&self) -> Edit {
+        match self.clone() {
+            Edit::AddNotes { difficulty, notes } => Edit::RemoveNotes { difficulty, notes },
+            Edit::RemoveNotes { difficulty, notes } => Edit::AddNotes { difficulty, notes },
+            Edit::ReplaceNotes {
+                difficulty,
+                before,
+                after,
+            } => Edit::ReplaceNotes {
+                difficulty,
+                before: after,
+                after: before,
+            },
+            Edit::SetBeatmapInfo {
+                difficulty,
+                before,
+                after,
+            } => Edit::SetBeatmapInfo {
+                difficulty,
+                before: after,
+                after: before,
+            },
+            Edit::SetMeta { before, after } => Edit::SetMeta {
+                before: after,
+                after: before,
+            },
+            Edit::SetDifficulties { before, after } => Edit::SetDifficulties {
+                before: after,
+                after: before,
+            },
+            Edit::SetPackageFiles { changes } => Edit::SetPackageFiles {
+                changes: changes
+                    .into_iter()
+                    .rev()
+                    .map(|c| FileChange {
+                        name: c.name,
+                        before: c.after,
+                        after: c.before,
+                    })
+                    .collect(),
+            },
+            Edit::Batch { label, edits } => Edit::Batch {
+                label,
+                edits: edits.iter().rev().map(Edit::inverse).collect(),
+            },
+        }
+    }
+
+    /// Whether applying or undoing this edit changes the package's other files.
+    pub fn touches_package_files(&self) -> bool {
+        match self {
+            Edit::SetPackageFiles { .. } => true,
+            Edit::Batch { edits, .. } => edits.iter().any(Edit::touches_package_files),
+            _ => false,
+        }
+    }
+
+    pub fn apply(&self, doc: &mut Document) {
+        match self {
+            Edit::AddNotes { difficulty, notes } => {
+                select_difficulty(doc, *difficulty);
+                doc.beatmap.insert_notes(notes.iter().cloned());
+            }
+            Edit::RemoveNotes { difficulty, notes } => {
+                select_difficulty(doc, *difficulty);
+                doc.beatmap.remove_notes(notes);
+            }
+            Edit::ReplaceNotes {
+                difficulty,
+                before,
+                after,
+            } => {
+                select_difficulty(doc, *difficulty);
+                doc.beatmap.remove_notes(before);
+                doc.beatmap.insert_notes(after.iter().cloned());
+            }
+            Edit::SetBeatmapInfo {
+                difficulty, after, ..
+            } => {
+                select_difficulty(doc, *difficulty);
+                after.apply_to(doc.beatmap);
+            }
+            Edit::SetMeta { after, .. } => {
+                let difficulties = std::mem::take(&mut doc.meta.difficulties);
+                *doc.meta = (**after).clone();
+                doc.meta.difficulties = difficulties;
+            }
+            Edit::SetDifficulties { after, .. } => after.restore(doc),
+            Edit::SetPackageFiles { changes } => {
+                let Some(pkg) = doc.package.as_mut() else {
+                    return;
+                };
+                for c in changes {
+                    match &c.after {
+                        Some(bytes) => {
+                            pkg.other_files.insert(c.name.clone(), bytes.clone());
+                        }
+                        None => {
+                            pkg.other_files.remove(&c.name);
+                        }
+                    }
+                }
+                doc.meta.hasCustomHitsounds = has_custom_samples(pkg);
+            }
+            Edit::Batch { edits, .. } => {
+                for edit in edits {
+                    edit.apply(doc);
+                }
+            }
+        }
+    }
+
+    /// Folds `next` into `self` if both edit the same thing, keeping the original `before`.
+    fn merge(&mut self, next: &Edit) -> bool {
+        match (self, next) {
+            (
+                Edit::SetBeatmapInfo {
+                    difficulty, after, ..
+                },
+                Edit::SetBeatmapInfo {
+                    difficulty: next_difficulty,
+                    after: next_after,
+                    ..
+                },
+            ) if difficulty == next_difficulty => {
+                *after = next_after.clone();
+                true
+            }
+            // Consecutive changes to the same notes, e.g. while dragging a volume slider.
+            (
+                Edit::ReplaceNotes {
+                    difficulty, after, ..
+                },
+                Edit::ReplaceNotes {
+                    difficulty: next_difficulty,
+                    before: next_before,
+                    after: next_after,
+                },
+            ) if difficulty == next_difficulty
+                && after.len() == next_before.len()
+                && next_before.iter().all(|n| after.contains(n)) =>
+            {
+                *after = next_after.clone();
+                true
+            }
+            // Typing into one field, but not moving on to another one.
+            (
+                Edit::SetMeta { before, after },
+                Edit::SetMeta {
+                    before: next_before,
+                    after: next_after,
+                },
+            ) if changed_meta_fields(before, after) == changed_meta_fields(next_before, next_after) => {
+                *after = next_after.clone();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The `Meta` fields that differ between `a` and `b`. Timing points and the BPM and offset
+/// mirrored from them count as one field.
+fn changed_meta_fields(a: &Meta, b: &Meta) -> Vec<&'static str> {
+    let fields = [
+        ("songName", a.songName != b.songName),
+        ("artistName", a.artistName != b.artistName),
+        ("mapper", a.mapper != b.mapper),
+        ("description", a.description != b.description),
+        ("tags", a.tags != b.tags),
+        ("language", a.language != b.language),
+        ("explicit", a.explicit != b.explicit),
+        ("audioFile", a.audioFile != b.audioFile),
+        ("backgroundFiles", a.backgroundFiles != b.backgroundFiles),
+        ("videoFile", a.videoFile != b.videoFile),
+        ("videoStartTime", a.videoStartTime != b.videoStartTime),
+        (
+            "timing",
+            a.timingPoints != b.timingPoints || a.bpm != b.bpm || a.offset != b.offset,
+        ),
+        ("previewTime", a.previewTime != b.previewTime),
+        ("hasCustomHitsounds", a.hasCustomHitsounds != b.hasCustomHitsounds),
+    ];
+    fields.into_iter().filter(|(_, changed)| *changed).map(|(name, _)| name).collect()
+}
+
+/// Makes `idx` the working difficulty, flushing the current beatmap back into the package.
+/// Edits remember which difficulty they were made on so undo never lands on the wrong one.
+fn select_difficulty(doc: &mut Document, idx: usize) {
+    if *doc.selected_difficulty == idx {
+        return;
+    }
+    let Some(pkg) = doc.package.as_mut() else {
+        return;
+    };
+    if idx >= pkg.difficulties.len() {
+        return;
+    }
+    if let Some(cur) = pkg.difficulties.get_mut(*doc.selected_difficulty) {
+        cur.beatmap = doc.beatmap.clone();
+    }
+    *doc.selected_difficulty = idx;
+    *doc.beatmap = pkg.difficulties[idx].beatmap.clone();
+}
+
+#[derive(Default)]
+pub struct History {
+    undo_stack: Vec<Edit>,
+    redo_stack: Vec<Edit>,
+    last_push_s: Option<f64>,
+}
+
+impl History {
+    /// Applies `edit` to the document and records it.
+    pub fn execute(&mut self, edit: Edit, doc: &mut Document) {
+        edit.apply(doc);
+        self.push(edit);
+    }
+
+    /// Applies `edit` and records it with [`History::push_merging`].
+    pub fn execute_merging(&mut self, edit: Edit, doc: &mut Document, now_s: f64) {
+        edit.apply(doc);
+        self.push_merging(edit, now_s);
+    }
+
+    /// Records an edit whose effect has already been applied (e.g. by an egui widget).
+    pub fn push(&mut self, edit: Edit) {
+        self.redo_stack.clear();
+        self.undo_stack.push(edit);
+        if self.undo_stack.len() > MAX_HISTORY {
+            self.undo_stack.remove(0);
+        }
+        self.last_push_s = None;
+    }
+
+    /// Like [`History::push`], but merges into the previous entry when it edited the same
+    /// thing less than [`MERGE_WINDOW_S`] ago.
+    pub fn push_merging(&mut self, edit: Edit, now_s: f64) {
+        let recent = self
+            .last_push_s
+            .is_some_and(|t| now_s - t < MERGE_WINDOW_S);
+        let merged = recent
+            && self.redo_stack.is_empty()
+            && self
+                .undo_stack
+                .last_mut()
+                .is_some_and(|last| last.merge(&edit));
+        if !merged {
+            self.push(edit);
+        }
+        self.last_push_s = Some(now_s);
+    }
+
+    /// The edit [`History::undo`] would revert next.
+    pub fn next_undo(&self) -> Option<&Edit> {
+        self.undo_stack.last()
+    }
+
+    /// The edit [`History::redo`] would apply next.
+    pub fn next_redo(&self) -> Option<&Edit> {
+        self.redo_stack.last()
+    }
+
+    pub fn undo(&mut self, doc: &mut Document) -> Option<&'static str> {
+        let edit = self.undo_stack.pop()?;
+        edit.inverse().apply(doc);
+        let label = edit.label();
+        self.redo_stack.push(edit);
+        self.last_push_s = None;
+        Some(label)
+    }
+
+    pub fn redo(&mut self, doc: &mut Document) -> Option<&'static str> {
+        let edit = self.redo_stack.pop()?;
+        edit.apply(doc);
+        let label = edit.label();
+        self.undo_stack.push(edit);
+        self.last_push_s = None;
+        Some(label)
+    }
+
+    /// Stops the next [`History::push_merging`] from folding into the current entry.
+    pub fn seal(&mut self) {
+        self.last_push_s = None;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.last_push_s = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::MetaDifficulty;
+    use std::collections::BTreeMap;
+
+    /// Plain-value stand-in for the editor's document.
+    struct Doc {
+        beatmap: Beatmap,
+        meta: Meta,
+        package: Option<RtmPackage>,
+        selected: usize,
+    }
+
+    impl Doc {
+        fn new() -> Self {
+            let difficulty = |name: &str, notes: Vec<BeatmapNote>| RtmDifficulty {
+                meta: MetaDifficulty {
+                    name: name.to_string(),
+                    filename: format!("{}.json", name.to_lowercase()),
+                },
+                beatmap: Beatmap {
+                    name: name.to_string(),
+                    notes,
+                    ..Beatmap::new()
+                },
+            };
+            let difficulties = vec![
+                difficulty("Easy", vec![BeatmapNote::tap("a".to_string(), 100)]),
+                difficulty("Hard", vec![BeatmapNote::tap("s".to_string(), 200)]),
+            ];
+            let meta = Meta {
+                songName: "Song".to_string(),
+                difficulties: difficulties.iter().map(|d| d.meta.clone()).collect(),
+                ..Meta::default()
+            };
+            Doc {
+                beatmap: difficulties[0].beatmap.clone(),
+                meta: meta.clone(),
+                package: Some(RtmPackage {
+                    meta,
+                    difficulties,
+                    other_files: BTreeMap::new(),
+                }),
+                selected: 0,
+            }
+        }
+
+        fn doc(&mut self) -> Document<'_> {
+            Document {
+                beatmap: &mut self.beatmap,
+                meta: &mut self.meta,
+                package: self.package.as_mut(),
+                selected_difficulty: &mut self.selected,
+            }
+        }
+
+        /// Everything an edit can change, with the working beatmap flushed into the package.
+        fn state(&self) -> String {
+            let mut difficulties = self.package.as_ref().unwrap().difficulties.clone();
+            difficulties[self.selected].beatmap = self.beatmap.clone();
+            let beatmaps: Vec<&Beatmap> = difficulties.iter().map(|d| &d.beatmap).collect();
+            let names: Vec<&MetaDifficulty> = difficulties.iter().map(|d| &d.meta).collect();
+            let files = &self.package.as_ref().unwrap().other_files;
+            format!("{:?}|{:?}|{:?}|{:?}", beatmaps, names, self.meta, files)
+        }
+    }
+
+    /// Executes `edit`, then checks that undo restores the original state and redo the
+    /// edited one.
+    fn round_trip(edit: Edit, check: impl Fn(&Doc)) {
+        let mut d = Doc::new();
+        let original = d.state();
+        let mut history = History::default();
+
+        history.execute(edit, &mut d.doc());
+        check(&d);
+        let edited = d.state();
+        assert_ne!(original, edited);
+
+        assert!(history.undo(&mut d.doc()).is_some());
+        assert_eq!(d.state(), original);
+        assert!(history.redo(&mut d.doc()).is_some());
+        assert_eq!(d.state(), edited);
+        check(&d);
+    }
+
+    #[test]
+    fn add_notes_round_trip() {
+        let note = BeatmapNote::tap("d".to_string(), 50);
+        round_trip(
+            Edit::AddNotes {
+                difficulty: 0,
+                notes: vec![note.clone()],
+            },
+            |d| assert_eq!(d.beatmap.notes[0], note),
+        );
+    }
+
+    #[test]
+    fn remove_notes_round_trip() {
+        round_trip(
+            Edit::RemoveNotes {
+                difficulty: 0,
+                notes: vec![BeatmapNote::tap("a".to_string(), 100)],
+            },
+            |d| assert!(d.beatmap.notes.is_empty()),
+        );
+    }
+
+    #[test]
+    fn replace_notes_round_trip() {
+        round_trip(
+            Edit::ReplaceNotes {
+                difficulty: 0,
+                before: vec![BeatmapNote::tap("a".to_string(), 100)],
+                after: vec![BeatmapNote::hold("a".to_string(), 100, 400)],
+            },
+            |d| assert_eq!(d.beatmap.notes[0].note_type, "hold"),
+        );
+    }
+
+    #[test]
+    fn edits_on_another_difficulty_switch_to_it() {
+        round_trip(
+            Edit::AddNotes {
+                difficulty: 1,
+                notes: vec![BeatmapNote::tap("f".to_string(), 300)],
+            },
+            |d| {
+                assert_eq!(d.selected, 1);
+                assert_eq!(d.beatmap.notes.len(), 2);
+            },
+        );
+    }
+
+    #[test]
+    fn set_beatmap_info_round_trip() {
+        let before = BeatmapInfo::of(&Doc::new().beatmap);
+        let after = BeatmapInfo {
+            overall_difficulty: 8.0,
+            ..before.clone()
+        };
+        round_trip(
+            Edit::SetBeatmapInfo {
+                difficulty: 0,
+                before,
+                after,
+            },
+            |d| assert_eq!(d.beatmap.overallDifficulty, 8.0),
+        );
+    }
+
+    #[test]
+    fn set_meta_round_trip_keeps_difficulties() {
+        let before = Doc::new().meta;
+        let after = Meta {
+            songName: "Other".to_string(),
+            difficulties: Vec::new(),
+            ..before.clone()
+        };
+        round_trip(
+            Edit::SetMeta {
+                before: Box::new(before),
+                after: Box::new(after),
+            },
+            |d| {
+                assert_eq!(d.meta.songName, "Other");
+                assert_eq!(d.meta.difficulties.len(), 2);
+            },
+        );
+    }
+
+    #[test]
+    fn set_difficulties_round_trip() {
+        let mut d = Doc::new();
+        let before = DifficultySnapshot::capture(&d.doc());
+        let mut after = before.clone();
+        after.difficulties.remove(0);
+        after.selected = 0;
+        round_trip(Edit::SetDifficulties { before, after }, |d| {
+            assert_eq!(d.meta.difficulties.len(), 1);
+            assert_eq!(d.beatmap.name, "Hard");
+        });
+    }
+
+    #[test]
+    fn set_package_files_round_trip() {
+        round_trip(
+            Edit::SetPackageFiles {
+                changes: vec![FileChange {
+                    name: "soft-hitclap.wav".to_string(),
+                    before: None,
+                    after: Some(vec![1, 2, 3]),
+                }],
+            },
+            |d| {
+                let pkg = d.package.as_ref().unwrap();
+                assert!(pkg.other_files.contains_key("soft-hitclap.wav"));
+                assert!(d.meta.hasCustomHitsounds);
+            },
+        );
+    }
+
+    #[test]
+    fn batch_round_trip_is_one_step() {
+        let before = BeatmapInfo::of(&Doc::new().beatmap);
+        let after = BeatmapInfo {
+            bg_file: "bg.png".to_string(),
+            ..before.clone()
+        };
+        let edit = Edit::Batch {
+            label: "Import background",
+            edits: vec![
+                Edit::SetPackageFiles {
+                    changes: vec![FileChange {
+                        name: "bg.png".to_string(),
+                        before: None,
+                        after: Some(vec![1]),
+                    }],
+                },
+                Edit::SetBeatmapInfo {
+                    difficulty: 0,
+                    before,
+                    after,
+                },
+            ],
+        };
+        assert_eq!(edit.label(), "Import background");
+        assert!(edit.touches_package_files());
+        assert!(!Edit::AddNotes { difficulty: 0, notes: Vec::new() }.touches_package_files());
+        round_trip(edit, |d| {
+            assert!(d.package.as_ref().unwrap().other_files.contains_key("bg.png"));
+            assert_eq!(d.beatmap.bgFile, "bg.png");
+        });
+    }
+
+    fn meta_edit(d: &Doc, song: &str) -> Edit {
+        Edit::SetMeta {
+            before: Box::new(d.meta.clone()),
+            after: Box::new(Meta {
+                songName: song.to_string(),
+                ..d.meta.clone()
+            }),
+        }
+    }
+
+    #[test]
+    fn merging_within_window_is_one_undo_step() {
+        let mut d = Doc::new();
+        let mut history = History::default();
+        for (i, song) in ["S", "So", "Son"].iter().enumerate() {
+            let edit = meta_edit(&d, song);
+            history.execute_merging(edit, &mut d.doc(), i as f64 * 0.5);
+        }
+        assert_eq!(d.meta.songName, "Son");
+
+        history.undo(&mut d.doc());
+        assert_eq!(d.meta.songName, "Song");
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn merging_stops_after_window_or_seal() {
+        let mut d = Doc::new();
+        let mut history = History::default();
+        let edit = meta_edit(&d, "A");
+        history.execute_merging(edit, &mut d.doc(), 0.0);
+        let edit = meta_edit(&d, "B");
+        history.execute_merging(edit, &mut d.doc(), MERGE_WINDOW_S + 0.1);
+        history.seal();
+        let edit = meta_edit(&d, "C");
+        history.execute_merging(edit, &mut d.doc(), MERGE_WINDOW_S + 0.2);
+
+        history.undo(&mut d.doc());
+        assert_eq!(d.meta.songName, "B");
+        history.undo(&mut d.doc());
+        assert_eq!(d.meta.songName, "A");
+        history.undo(&mut d.doc());
+        assert_eq!(d.meta.songName, "Song");
+    }
+
+    #[test]
+    fn merging_stops_when_another_field_is_edited() {
+        let mut d = Doc::new();
+        let mut history = History::default();
+        let edit = meta_edit(&d, "Songs");
+        history.execute_merging(edit, &mut d.doc(), 0.0);
+        let edit = Edit::SetMeta {
+            before: Box::new(d.meta.clone()),
+            after: Box::new(Meta {
+                artistName: "Artist".to_string(),
+                ..d.meta.clone()
+            }),
+        };
+        history.execute_merging(edit, &mut d.doc(), 0.2);
+        let edit = meta_edit(&d, "Songs!");
+        history.execute_merging(edit, &mut d.doc(), 0.4);
+
+        history.undo(&mut d.doc());
+        assert_eq!((d.meta.songName.as_str(), d.meta.artistName.as_str()), ("Songs", "Artist"));
+        history.undo(&mut d.doc());
+        assert_eq!((d.meta.songName.as_str(), d.meta.artistName.as_str()), ("Songs", ""));
+        history.undo(&mut d.doc());
+        assert_eq!(d.meta.songName, "Song");
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn new_edit_clears_redo() {
+        let mut d = Doc::new();
+        let mut history = History::default();
+        let edit = meta_edit(&d, "A");
+        history.execute(edit, &mut d.doc());
+        history.undo(&mut d.doc());
+        assert!(history.can_redo());
+        let edit = meta_edit(&d, "B");
+        history.execute(edit, &mut d.doc());
+        assert!(!history.can_redo());
+    }
+}
@@ -0,0 +1,249 @@
+use crate::{
+    audio_util,
+    data::{BeatmapNote, HitsoundPart, SoundConfig},
+    editor_state::EditorState,
+    hitsound_util::{sample_name, HIT_SOUNDS, HOLD_LOOP, SAMPLE_SETS},
+    selection::NoteId,
+};
+use bevy::prelude::*;
+use bevy_kira_audio::{AudioChannel, AudioControl, AudioInstance, AudioSource, AudioTween};
+use kira::sound::static_sound::StaticSoundData;
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::path::PathBuf;
+
+/// Hitsounds play on their own channel so they don't interfere with the song instance.
+#[derive(Resource)]
+pub struct HitsoundChannel;
+
+
+/// Sample handles keyed by name, e.g. `soft-hitclap` or `drum-holdloop`.
+#[derive(Resource, Default)]
+pub struct HitsoundBank {
+    /// Synthesized samples, used when the package has no custom sample of that name.
+    defaults: HashMap<String, Handle<AudioSource>>,
+    custom: HashMap<String, Handle<AudioSource>>,
+    /// The package path and `EditorState::package_files_revision` `custom` was loaded from.
+    custom_key: Option<(PathBuf, u64)>,
+    /// The package entries `custom` was loaded from.
+    custom_entries: Vec<String>,
+}
+
+impl HitsoundBank {
+    pub fn get(&self, name: &str) -> Option<Handle<AudioSource>> {
+        self.custom.get(name).or_else(|| self.defaults.get(name)).cloned()
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct HitsoundPlayback {
+    last_ms: Option<u32>,
+    loops: HashMap<NoteId, Handle<AudioInstance>>,
+}
+
+/// Built-in stand-ins for the default skin samples. The sample set shifts the pitch and
+/// softens the attack so the sets can be told apart.
+fn synth_default(set: &str, sound: &str) -> StaticSoundData {
+    let (pitch, gain) = match set {
+        "soft" => (0.7, 0.7),
+        "drum" => (0.4, 1.0),
+        _ => (1.0, 0.9),
+    };
+    let mut noise = audio_util::Noise::new(0x5eed);
+    match sound {
+        "hitnormal" => audio_util::synth_sound(0.08, |t| {
+            let tone = (TAU * 1800.0 * pitch * t).sin() * (-t / 0.015).exp() * 0.6;
+            let click = noise.sample() * (-t / 0.004).exp() * 0.3;
+            (tone + click) * gain
+        }),
+        "hitclap" => audio_util::synth_sound(0.15, |t| {
+            // A few quick bursts, like hands not quite in time.
+            let env: f32 = [0.0, 0.009, 0.018]
+                .iter()
+                .filter(|&&at| t >= at)
+                .map(|&at| (-(t - at) / 0.012).exp())
+                .sum();
+            noise.sample() * env * 0.35 * gain
+        }),
+        "hitwhistle" => audio_util::synth_sound(0.22, |t| {
+            let attack = (t / 0.01).min(1.0);
+            let vibrato = (TAU * 30.0 * t).sin() * 0.004;
+            (TAU * 2400.0 * pitch * (t + vibrato)).sin() * attack * (-t / 0.08).exp() * 0.4 * gain
+        }),
+        "hitfinish" => audio_util::synth_sound(0.7, |t| {
+            let wash = noise.sample() * (-t / 0.2).exp() * 0.3;
+            let ring = (TAU * 600.0 * pitch * t).sin() * (-t / 0.3).exp() * 0.2;
+            (wash + ring) * gain
+        }),
+        // Exactly 0.5s of a whole number of cycles at every pitch, so the loop is seamless.
+        _ => audio_util::synth_sound(0.5, |t| (TAU * 440.0 * pitch * t).sin() * 0.15 * gain),
+    }
+}
+
+pub fn setup_hitsounds(mut sources: ResMut<Assets<AudioSource>>, mut bank: ResMut<HitsoundBank>) {
+    for set in SAMPLE_SETS {
+        for sound in HIT_SOUNDS.iter().copied().chain([HOLD_LOOP]) {
+            let handle = sources.add(AudioSource {
+                sound: synth_default(set, sound),
+            });
+            bank.defaults.insert(format!("{}-{}", set, sound), handle);
+        }
+    }
+}
+
+/// Loads the package's own samples when `Meta::hasCustomHitsounds` is set, reloading when
+/// the package or its sample files change.
+pub fn refresh_custom_hitsounds(
+    state: Res<EditorState>,
+    asset_server: Res<AssetServer>,
+    mut bank: ResMut<HitsoundBank>,
+) {
+    let key = match &state.rtm_file_path {
+        Some(path) if state.rtm_package.is_some() && state.meta.hasCustomHitsounds => {
+            Some((path.clone(), state.package_files_revision))
+        }
+        _ => None,
+    };
+    if key == bank.custom_key {
+        return;
+    }
+
+    let previous = bank.custom_key.take();
+    let previous_entries = std::mem::take(&mut bank.custom_entries);
+    bank.custom.clear();
+    if let (Some(pkg), Some((path, _))) = (&state.rtm_package, &key) {
+        let entries: Vec<String> = pkg
+            .other_files
+            .keys()
+            .filter(|name| sample_name(name).is_some())
+            .cloned()
+            .collect();
+        let same_package = previous.as_ref().is_some_and(|(p, _)| p == path);
+        for entry in &entries {
+            match audio_util::extract_entry(pkg, path, entry) {
+                Ok(rel) => {
+                    let name = sample_name(entry).unwrap_or_default();
+                    bank.custom.insert(name, asset_server.load(rel.clone()));
+                    // The asset server caches by path, so a replaced file has to be reloaded.
+                    if same_package && previous_entries.contains(entry) {
+                        asset_server.reload(rel);
+                    }
+                }
+                Err(err) => warn!("Custom hitsound {} failed: {}", entry, err),
+            }
+        }
+        bank.custom_entries = entries;
+    }
+    bank.custom_key = key;
+}
+
+fn play_hit(
+    channel: &AudioChannel<HitsoundChannel>,
+    bank: &HitsoundBank,
+    set: &str,
+    sounds: Option<&SoundConfig>,
+    volume: u32,
+) {
+    if volume == 0 {
+        return;
+    }
+    let flags = match sounds {
+        Some(s) => [s.hitnormal, s.hitclap, s.hitwhistle, s.hitfinish],
+        None => [true, false, false, false],
+    };
+    for (sound, on) in HIT_SOUNDS.iter().zip(flags) {
+        if !on {
+            continue;
+        }
+        if let Some(handle) = bank.get(&format!("{}-{}", set, sound)) {
+            channel.play(handle).with_volume(volume as f64 / 100.0);
+        }
+    }
+}
+
+fn play_part(
+    channel: &AudioChannel<HitsoundChannel>,
+    bank: &HitsoundBank,
+    note: &BeatmapNote,
+    part: Option<&HitsoundPart>,
+) {
+    let hs = &note.hitsound;
+    match part {
+        Some(p) => play_hit(channel, bank, &hs.sampleSet, p.sounds.as_ref(), p.volume),
+        None => play_hit(channel, bank, &hs.sampleSet, hs.sounds.as_ref(), hs.volume.unwrap_or(100)),
+    }
+}
+
+fn stop_loop(instances: &mut Assets<AudioInstance>, handle: &Handle<AudioInstance>) {
+    if let Some(instance) = instances.get_mut(handle) {
+        instance.stop(AudioTween::default());
+    }
+}
+
+/// Plays the hitsounds of notes the playhead passed since the last frame, and keeps a
+/// looping sound running under each hold note being played through.
+pub fn hitsound_playback_system(
+    state: Res<EditorState>,
+    bank: Res<HitsoundBank>,
+    mut playback: ResMut<HitsoundPlayback>,
+    channel: Res<AudioChannel<HitsoundChannel>>,
+    mut instances: ResMut<Assets<AudioInstance>>,
+) {
+    let window = audio_util::playback_window(&mut playback.last_ms, &state)
+        .filter(|_| state.hitsounds_enabled);
+
+    let Some((prev, now)) = window else {
+        for (_, handle) in playback.loops.drain() {
+            stop_loop(&mut instances, &handle);
+        }
+        return;
+    };
+    if now == prev {
+        return;
+    }
+
+    let passed = |t: u32| t > prev && t <= now;
+    for note in &state.beatmap.notes {
+        let hs = &note.hitsound;
+        let start = note.get_start_time();
+        if note.note_type != "hold" {
+            if passed(start) {
+                play_part(&channel, &bank, note, None);
+            }
+            continue;
+        }
+
+        let end = note.get_end_time();
+        if passed(start) {
+            play_part(&channel, &bank, note, hs.start.as_ref());
+            let loop_volume = hs.hold.as_ref().map(|h| h.volume).unwrap_or(0);
+            if loop_volume > 0 && end > now {
+                let set = hs.hold.as_ref().map(|h| h.loop_field.as_str()).unwrap_or("normal");
+                if let Some(sample) = bank.get(&format!("{}-{}", set, HOLD_LOOP)) {
+                    let handle = channel
+                        .play(sample)
+                        .looped()
+                        .with_volume(loop_volume as f64 / 100.0)
+                        .handle();
+                    playback.loops.insert(NoteId::of(note), handle);
+                }
+            }
+        }
+        if passed(end) {
+            play_part(&channel, &bank, note, hs.end.as_ref());
+        }
+    }
+
+    // Stop loops whose hold has ended, or which were moved or deleted while playing.
+    let finished: Vec<NoteId> = playback
+        .loops
+        .keys()
+        .filter(|id| id.end <= now || !state.beatmap.notes.iter().any(|n| NoteId::of(n) == **id))
+        .cloned()
+        .collect();
+    for id in finished {
+        if let Some(handle) = playback.loops.remove(&id) {
+            stop_loop(&mut instances, &handle);
+        }
+    }
+}
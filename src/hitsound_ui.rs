@@ -0,0 +1,301 @@
+use crate::{
+    data::{BeatmapNote, Hitsound, HitsoundPart, HoldConfig, SoundConfig},
+    editor_state::EditorState,
+    history::Edit,
+    hitsound_util::SAMPLE_SETS,
+};
+use bevy_egui::egui;
+
+/// Which notes the hitsound editor applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HitsoundTarget {
+    #[default]
+    Selection,
+    /// Every note overlapping `hitsound_range_ms` on one of `hitsound_keys` (all keys if empty).
+    Range,
+}
+
+fn default_sounds() -> SoundConfig {
+    SoundConfig {
+        hitnormal: true,
+        hitclap: false,
+        hitwhistle: false,
+        hitfinish: false,
+    }
+}
+
+fn default_part() -> HitsoundPart {
+    HitsoundPart {
+        volume: 100,
+        sounds: None,
+    }
+}
+
+fn default_hold() -> HoldConfig {
+    HoldConfig {
+        volume: 70,
+        loop_field: "normal".to_string(),
+    }
+}
+
+/// Copies the flags that differ between `before` and `after` into `target`, leaving the
+/// others as they were.
+fn apply_sounds(before: &Option<SoundConfig>, after: &Option<SoundConfig>, target: &mut Option<SoundConfig>) {
+    let b = before.clone().unwrap_or_else(default_sounds);
+    let a = after.clone().unwrap_or_else(default_sounds);
+    if a == b {
+        return;
+    }
+    let t = target.get_or_insert_with(default_sounds);
+    if a.hitnormal != b.hitnormal {
+        t.hitnormal = a.hitnormal;
+    }
+    if a.hitclap != b.hitclap {
+        t.hitclap = a.hitclap;
+    }
+    if a.hitwhistle != b.hitwhistle {
+        t.hitwhistle = a.hitwhistle;
+    }
+    if a.hitfinish != b.hitfinish {
+        t.hitfinish = a.hitfinish;
+    }
+}
+
+fn apply_part(before: &Option<HitsoundPart>, after: &Option<HitsoundPart>, target: &mut Option<HitsoundPart>) {
+    let b = before.clone().unwrap_or_else(default_part);
+    let a = after.clone().unwrap_or_else(default_part);
+    if a == b {
+        return;
+    }
+    let t = target.get_or_insert_with(default_part);
+    if a.volume != b.volume {
+        t.volume = a.volume;
+    }
+    apply_sounds(&b.sounds, &a.sounds, &mut t.sounds);
+}
+
+/// Applies the fields changed between `before` and `after` to `target`, so a multi-note
+/// edit only touches what was changed and keeps per-note differences elsewhere.
+pub fn apply_changed_fields(before: &Hitsound, after: &Hitsound, target: &mut Hitsound) {
+    if after.sampleSet != before.sampleSet {
+        target.sampleSet = after.sampleSet.clone();
+    }
+    if after.volume != before.volume {
+        target.volume = after.volume;
+    }
+    apply_sounds(&before.sounds, &after.sounds, &mut target.sounds);
+    apply_part(&before.start, &after.start, &mut target.start);
+    apply_part(&before.end, &after.end, &mut target.end);
+
+    let b = before.hold.clone().unwrap_or_else(default_hold);
+    let a = after.hold.clone().unwrap_or_else(default_hold);
+    if a != b {
+        let t = target.hold.get_or_insert_with(default_hold);
+        if a.volume != b.volume {
+            t.volume = a.volume;
+        }
+        if a.loop_field != b.loop_field {
+            t.loop_field = a.loop_field.clone();
+        }
+    }
+}
+
+fn target_notes(state: &EditorState) -> Vec<BeatmapNote> {
+    match state.hitsound_target {
+        HitsoundTarget::Selection => state.selection.notes(&state.beatmap),
+        HitsoundTarget::Range => {
+            let (from, to) = state.hitsound_range_ms;
+            let (from, to) = (from.min(to), from.max(to));
+            let keys: Vec<String> = state
+                .hitsound_keys
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .map(|c| c.to_lowercase().to_string())
+                .collect();
+            state
+                .beatmap
+                .notes
+                .iter()
+                .filter(|n| n.get_start_time() <= to && n.get_end_time() >= from)
+                .filter(|n| keys.is_empty() || keys.contains(&n.key.to_lowercase()))
+                .cloned()
+                .collect()
+        }
+    }
+}
+
+fn sample_set_combo(ui: &mut egui::Ui, id: &str, value: &mut String) {
+    egui::ComboBox::from_id_source(id)
+        .selected_text(value.clone())
+        .show_ui(ui, |ui| {
+            for set in SAMPLE_SETS {
+                ui.selectable_value(value, set.to_string(), set);
+            }
+        });
+}
+
+fn sounds_row(ui: &mut egui::Ui, sounds: &mut Option<SoundConfig>) {
+    let mut s = sounds.clone().unwrap_or_else(default_sounds);
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut s.hitnormal, "Normal");
+        ui.checkbox(&mut s.hitclap, "Clap");
+        ui.checkbox(&mut s.hitwhistle, "Whistle");
+        ui.checkbox(&mut s.hitfinish, "Finish");
+    });
+    if sounds.clone().unwrap_or_else(default_sounds) != s {
+        *sounds = Some(s);
+    }
+}
+
+fn part_rows(ui: &mut egui::Ui, label: &str, part: &mut Option<HitsoundPart>) {
+    let mut p = part.clone().unwrap_or_else(default_part);
+    ui.label(label);
+    ui.add(egui::Slider::new(&mut p.volume, 0..=100).text("Volume"));
+    sounds_row(ui, &mut p.sounds);
+    if part.clone().unwrap_or_else(default_part) != p {
+        *part = Some(p);
+    }
+}
+
+/// Edits the hitsounds of the selected notes, or of every note in a time range and key set.
+/// Changes are applied field by field, so editing one field of a mixed selection leaves the
+/// notes' other fields alone.
+pub fn draw_hitsound_inspector(ui: &mut egui::Ui, state: &mut EditorState, now_s: f64) {
+    ui.heading("Hitsounds");
+
+    ui.horizontal(|ui| {
+        ui.radio_value(&mut state.hitsound_target, HitsoundTarget::Selection, "Selected notes");
+        ui.radio_value(&mut state.hitsound_target, HitsoundTarget::Range, "Range");
+    });
+
+    if state.hitsound_target == HitsoundTarget::Range {
+        let now_ms = state.current_time;
+        egui::Grid::new("hitsound_range_grid").num_columns(3).show(ui, |ui| {
+            ui.label("From (ms)");
+            ui.add(egui::DragValue::new(&mut state.hitsound_range_ms.0).speed(1));
+            if ui.small_button("⏵ Playhead").clicked() {
+                state.hitsound_range_ms.0 = now_ms;
+            }
+            ui.end_row();
+
+            ui.label("To (ms)");
+            ui.add(egui::DragValue::new(&mut state.hitsound_range_ms.1).speed(1));
+            if ui.small_button("⏵ Playhead").clicked() {
+                state.hitsound_range_ms.1 = now_ms;
+            }
+            ui.end_row();
+
+            ui.label("Keys");
+            ui.add(
+                egui::TextEdit::singleline(&mut state.hitsound_keys)
+                    .hint_text("all keys")
+                    .desired_width(120.0),
+            );
+            ui.end_row();
+        });
+    }
+
+    let targets = target_notes(state);
+    if targets.is_empty() {
+        ui.label(match state.hitsound_target {
+            HitsoundTarget::Selection => "No notes selected.",
+            HitsoundTarget::Range => "No notes in range.",
+        });
+        return;
+    }
+
+    let is_hold = |n: &&BeatmapNote| n.note_type == "hold";
+    let holds = targets.iter().filter(is_hold).count();
+    ui.label(format!(
+        "{} notes ({} taps, {} holds)",
+        targets.len(),
+        targets.len() - holds,
+        holds
+    ));
+    if targets.windows(2).any(|w| w[0].hitsound != w[1].hitsound) {
+        ui.weak("Values differ; showing the first note. Edits only change the field you touch.");
+    }
+
+    // (before, after) templates; whichever changes is applied to the matching notes.
+    let mut common = targets[0].hitsound.clone();
+    let common_before = common.clone();
+    ui.horizontal(|ui| {
+        ui.label("Sample set");
+        sample_set_combo(ui, "hitsound_sample_set", &mut common.sampleSet);
+    });
+
+    let tap_before = targets.iter().find(|n| n.note_type != "hold").map(|n| n.hitsound.clone());
+    let mut tap = tap_before.clone();
+    if let Some(hs) = tap.as_mut() {
+        ui.separator();
+        ui.label("Taps");
+        let mut has_volume = hs.volume.is_some();
+        let mut volume = hs.volume.unwrap_or(100);
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut has_volume, "Volume");
+            ui.add_enabled(has_volume, egui::Slider::new(&mut volume, 0..=100));
+        });
+        hs.volume = has_volume.then_some(volume);
+        sounds_row(ui, &mut hs.sounds);
+    }
+
+    let hold_before = targets.iter().find(is_hold).map(|n| n.hitsound.clone());
+    let mut hold = hold_before.clone();
+    if let Some(hs) = hold.as_mut() {
+        ui.separator();
+        part_rows(ui, "Hold start", &mut hs.start);
+
+        let mut cfg = hs.hold.clone().unwrap_or_else(default_hold);
+        ui.label("Hold loop");
+        ui.add(egui::Slider::new(&mut cfg.volume, 0..=100).text("Volume"));
+        ui.horizontal(|ui| {
+            ui.label("Loop sample");
+            sample_set_combo(ui, "hitsound_hold_loop", &mut cfg.loop_field);
+        });
+        if hs.hold.clone().unwrap_or_else(default_hold) != cfg {
+            hs.hold = Some(cfg);
+        }
+
+        part_rows(ui, "Hold end", &mut hs.end);
+    }
+
+    let common_changed = common != common_before;
+    let tap_changed = tap != tap_before;
+    let hold_changed = hold != hold_before;
+    if !(common_changed || tap_changed || hold_changed) {
+        return;
+    }
+
+    let mut before = Vec::new();
+    let mut after = Vec::new();
+    for n in &targets {
+        let mut edited = n.clone();
+        apply_changed_fields(&common_before, &common, &mut edited.hitsound);
+        let typed = if n.note_type == "hold" {
+            hold_before.as_ref().zip(hold.as_ref())
+        } else {
+            tap_before.as_ref().zip(tap.as_ref())
+        };
+        if let Some((b, a)) = typed {
+            apply_changed_fields(b, a, &mut edited.hitsound);
+        }
+        if edited != *n {
+            before.push(n.clone());
+            after.push(edited);
+        }
+    }
+    if after.is_empty() {
+        return;
+    }
+
+    let count = after.len();
+    state.apply_edit_merging(
+        Edit::ReplaceNotes {
+            difficulty: state.selected_difficulty,
+            before,
+            after,
+        },
+        now_s,
+    );
+    state.status = format!("Updated hitsounds on {} notes", count);
+}
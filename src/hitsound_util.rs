@@ -0,0 +1,78 @@
+use crate::{data::Beatmap, loader::RtmPackage};
+use std::collections::BTreeSet;
+
+pub const SAMPLE_SETS: [&str; 3] = ["normal", "soft", "drum"];
+pub const HIT_SOUNDS: [&str; 4] = ["hitnormal", "hitclap", "hitwhistle", "hitfinish"];
+pub const HOLD_LOOP: &str = "holdloop";
+pub const SAMPLE_EXTENSIONS: [&str; 4] = ["wav", "ogg", "mp3", "flac"];
+
+/// Every sample a package can provide: `<set>-<sound>` for each set, hit sound and the hold loop.
+pub fn all_sample_names() -> Vec<String> {
+    SAMPLE_SETS
+        .iter()
+        .flat_map(|set| {
+            HIT_SOUNDS
+                .iter()
+                .copied()
+                .chain([HOLD_LOOP])
+                .map(move |sound| format!("{}-{}", set, sound))
+        })
+        .collect()
+}
+
+/// The sample name a package file provides, e.g. `sounds/Soft-HitClap.wav` -> `soft-hitclap`.
+/// Custom samples follow osu!'s `<set>-<sound>.<ext>` naming.
+pub fn sample_name(file: &str) -> Option<String> {
+    let base = file.rsplit(['/', '\\']).next()?;
+    let (stem, ext) = base.rsplit_once('.')?;
+    if !SAMPLE_EXTENSIONS.contains(&ext.to_lowercase().as_str()) {
+        return None;
+    }
+    let name = stem.to_lowercase();
+    let (set, sound) = name.split_once('-')?;
+    let known = SAMPLE_SETS.contains(&set) && (HIT_SOUNDS.contains(&sound) || sound == HOLD_LOOP);
+    known.then_some(name)
+}
+
+pub fn has_custom_samples(pkg: &RtmPackage) -> bool {
+    pkg.other_files.keys().any(|name| sample_name(name).is_some())
+}
+
+/// Sample sets with at least one custom sample in the package.
+pub fn custom_sample_sets(pkg: &RtmPackage) -> BTreeSet<String> {
+    pkg.other_files
+        .keys()
+        .filter_map(|name| sample_name(name))
+        .filter_map(|name| name.split_once('-').map(|(set, _)| set.to_string()))
+        .collect()
+}
+
+/// Sample sets a beatmap's notes use, including hold loops.
+pub fn sample_sets_used(beatmap: &Beatmap) -> BTreeSet<String> {
+    beatmap
+        .notes
+        .iter()
+        .flat_map(|n| {
+            let loop_set = n.hitsound.hold.as_ref().map(|h| h.loop_field.to_lowercase());
+            std::iter::once(n.hitsound.sampleSet.to_lowercase()).chain(loop_set)
+        })
+        .collect()
+}
+
+/// Problems with the sample sets `beatmap` uses: sets the game doesn't know, and, when
+/// `custom_hitsounds` is on, sets the package has no custom samples for.
+pub fn sample_set_problems(pkg: &RtmPackage, custom_hitsounds: bool, beatmap: &Beatmap) -> Vec<String> {
+    let custom = custom_sample_sets(pkg);
+    let mut out = Vec::new();
+    for set in sample_sets_used(beatmap) {
+        if !SAMPLE_SETS.contains(&set.as_str()) {
+            out.push(format!("notes use unknown sample set '{}'", set));
+        } else if custom_hitsounds && !custom.contains(&set) {
+            out.push(format!(
+                "notes use sample set '{}' but the package has no {} samples",
+                set, set
+            ));
+        }
+    }
+    out
+}
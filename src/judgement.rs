@@ -0,0 +1,384 @@
+use crate::data::BeatmapNote;
+
+/// How far (ms) from a note's time a press or release may land for each grade, derived
+/// from the beatmap's overall difficulty like osu!mania's windows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HitWindows {
+    pub perfect: f32,
+    pub great: f32,
+    pub good: f32,
+    /// Presses earlier than `good` but within this window still take the note, as a miss.
+    pub miss: f32,
+}
+
+impl HitWindows {
+    pub fn from_od(od: f32) -> Self {
+        let od = od.clamp(0.0, 10.0);
+        HitWindows {
+            perfect: 64.0 - 3.0 * od,
+            great: 97.0 - 3.0 * od,
+            good: 127.0 - 3.0 * od,
+            miss: 188.0 - 3.0 * od,
+        }
+    }
+
+    /// The grade for a hit `offset_ms` away from the note, or `None` if it's outside `good`.
+    pub fn grade(&self, offset_ms: f32) -> Option<Grade> {
+        let off = offset_ms.abs();
+        if off <= self.perfect {
+            Some(Grade::Perfect)
+        } else if off <= self.great {
+            Some(Grade::Great)
+        } else if off <= self.good {
+            Some(Grade::Good)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grade {
+    Perfect,
+    Great,
+    Good,
+    Miss,
+}
+
+impl Grade {
+    pub const ALL: [Grade; 4] = [Grade::Perfect, Grade::Great, Grade::Good, Grade::Miss];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Grade::Perfect => "Perfect",
+            Grade::Great => "Great",
+            Grade::Good => "Good",
+            Grade::Miss => "Miss",
+        }
+    }
+
+    /// Share of a perfect hit this grade is worth towards accuracy.
+    fn weight(self) -> f32 {
+        match self {
+            Grade::Perfect => 1.0,
+            Grade::Great => 2.0 / 3.0,
+            Grade::Good => 1.0 / 3.0,
+            Grade::Miss => 0.0,
+        }
+    }
+}
+
+/// One judged press or release.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Judgement {
+    pub note: usize,
+    /// True for a hold note's release.
+    pub tail: bool,
+    pub grade: Grade,
+    /// Positive when late. `None` for notes that were never hit.
+    pub offset_ms: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NoteState {
+    Pending,
+    /// A hold whose head was hit and whose key is still down.
+    Holding,
+    Done,
+}
+
+/// Judges key presses and releases against a beatmap's notes. Times are map times in ms;
+/// feed events in time order and call [`Judge::advance`] as time passes so missed notes are
+/// judged.
+pub struct Judge {
+    notes: Vec<BeatmapNote>,
+    state: Vec<NoteState>,
+    windows: HitWindows,
+    pub judgements: Vec<Judgement>,
+    pub combo: u32,
+    pub max_combo: u32,
+}
+
+impl Judge {
+    /// Notes starting before `start_ms` are left out.
+    pub fn new(notes: &[BeatmapNote], od: f32, start_ms: u32) -> Self {
+        let mut notes: Vec<BeatmapNote> = notes
+            .iter()
+            .filter(|n| n.get_start_time() >= start_ms)
+            .cloned()
+            .collect();
+        notes.sort_by_key(|n| n.get_start_time());
+        Judge {
+            state: vec![NoteState::Pending; notes.len()],
+            notes,
+            windows: HitWindows::from_od(od),
+            judgements: Vec::new(),
+            combo: 0,
+            max_combo: 0,
+        }
+    }
+
+    pub fn windows(&self) -> HitWindows {
+        self.windows
+    }
+
+    fn judge(&mut self, note: usize, tail: bool, grade: Grade, offset_ms: Option<i64>) {
+        if grade == Grade::Miss {
+            self.combo = 0;
+        } else {
+            self.combo += 1;
+            self.max_combo = self.max_combo.max(self.combo);
+        }
+        self.judgements.push(Judgement {
+            note,
+            tail,
+            grade,
+            offset_ms,
+        });
+    }
+
+    /// A key went down at `time_ms`. Takes the earliest pending note on that key within the
+    /// miss window; presses near no note are ignored.
+    pub fn press(&mut self, key: &str, time_ms: u32) {
+        let t = time_ms as f32;
+        let w = self.windows;
+        let Some(idx) = (0..self.notes.len()).find(|&i| {
+            let start = self.notes[i].get_start_time() as f32;
+            self.state[i] == NoteState::Pending
+                && self.notes[i].key.eq_ignore_ascii_case(key)
+                && t >= start - w.miss
+                && t <= start + w.good
+        }) else {
+            return;
+        };
+
+        let offset = time_ms as i64 - self.notes[idx].get_start_time() as i64;
+        match w.grade(offset as f32) {
+            Some(grade) => {
+                self.judge(idx, false, grade, Some(offset));
+                self.state[idx] = if self.notes[idx].note_type == "hold" {
+                    NoteState::Holding
+                } else {
+                    NoteState::Done
+                };
+            }
+            // Far too early: the note is taken but missed, hold and all.
+            None => {
+                let hold = self.notes[idx].note_type == "hold";
+                self.judge(idx, false, Grade::Miss, Some(offset));
+                if hold {
+                    self.judge(idx, true, Grade::Miss, None);
+                }
+                self.state[idx] = NoteState::Done;
+            }
+        }
+    }
+
+    /// A key went up at `time_ms`. Judges the release of a hold being held on that key;
+    /// letting go before the end's `good` window breaks the hold.
+    pub fn release(&mut self, key: &str, time_ms: u32) {
+        let Some(idx) = (0..self.notes.len())
+            .find(|&i| self.state[i] == NoteState::Holding && self.notes[i].key.eq_ignore_ascii_case(key))
+        else {
+            return;
+        };
+        let offset = time_ms as i64 - self.notes[idx].get_end_time() as i64;
+        let grade = match self.windows.grade(offset as f32) {
+            Some(grade) => grade,
+            // Holding on past the end is fine, as in `advance`.
+            None if offset > 0 => Grade::Perfect,
+            None => Grade::Miss,
+        };
+        self.judge(idx, true, grade, Some(offset));
+        self.state[idx] = NoteState::Done;
+    }
+
+    /// Judges everything that can no longer be hit by `time_ms`: unpressed notes past their
+    /// `good` window miss, and holds still held past their end count as released on time.
+    pub fn advance(&mut self, time_ms: u32) {
+        let t = time_ms as f32;
+        for i in 0..self.notes.len() {
+            let hold = self.notes[i].note_type == "hold";
+            match self.state[i] {
+                NoteState::Pending if t > self.notes[i].get_start_time() as f32 + self.windows.good => {
+                    self.judge(i, false, Grade::Miss, None);
+                    if hold {
+                        self.judge(i, true, Grade::Miss, None);
+                    }
+                    self.state[i] = NoteState::Done;
+                }
+                NoteState::Holding if t > self.notes[i].get_end_time() as f32 + self.windows.good => {
+                    self.judge(i, true, Grade::Perfect, Some(0));
+                    self.state[i] = NoteState::Done;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn count(&self, grade: Grade) -> usize {
+        self.judgements.iter().filter(|j| j.grade == grade).count()
+    }
+
+    /// Accuracy so far, 0..1, or 1 before anything was judged.
+    pub fn accuracy(&self) -> f32 {
+        if self.judgements.is_empty() {
+            return 1.0;
+        }
+        let total: f32 = self.judgements.iter().map(|j| j.grade.weight()).sum();
+        total / self.judgements.len() as f32
+    }
+
+    /// True once every note has been judged.
+    pub fn finished(&self) -> bool {
+        self.state.iter().all(|s| *s == NoteState::Done)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tap(key: &str, time: u32) -> BeatmapNote {
+        BeatmapNote::tap(key.to_string(), time)
+    }
+
+    fn hold(key: &str, start: u32, end: u32) -> BeatmapNote {
+        BeatmapNote::hold(key.to_string(), start, end)
+    }
+
+    fn grades(judge: &Judge) -> Vec<(usize, bool, Grade)> {
+        judge.judgements.iter().map(|j| (j.note, j.tail, j.grade)).collect()
+    }
+
+    // OD 5: perfect 49ms, great 82ms, good 112ms, miss 173ms.
+
+    #[test]
+    fn windows_shrink_as_od_rises() {
+        let easy = HitWindows::from_od(0.0);
+        let hard = HitWindows::from_od(10.0);
+        assert_eq!(easy, HitWindows { perfect: 64.0, great: 97.0, good: 127.0, miss: 188.0 });
+        assert_eq!(hard, HitWindows { perfect: 34.0, great: 67.0, good: 97.0, miss: 158.0 });
+        // Out of range ODs are clamped.
+        assert_eq!(HitWindows::from_od(15.0), hard);
+        assert_eq!(HitWindows::from_od(-1.0), easy);
+
+        // The same 90ms offset is a Great at OD 0 but only a Good at OD 10.
+        assert_eq!(easy.grade(90.0), Some(Grade::Great));
+        assert_eq!(hard.grade(90.0), Some(Grade::Good));
+        assert_eq!(hard.grade(-100.0), None);
+    }
+
+    #[test]
+    fn early_and_late_presses_are_graded_by_distance() {
+        let mut judge = Judge::new(&[tap("a", 1000), tap("a", 2000), tap("a", 3000)], 5.0, 0);
+        judge.press("A", 960);
+        judge.press("a", 2070);
+        judge.press("a", 2900);
+        assert_eq!(
+            grades(&judge),
+            [(0, false, Grade::Perfect), (1, false, Grade::Great), (2, false, Grade::Good)]
+        );
+        let offsets: Vec<_> = judge.judgements.iter().map(|j| j.offset_ms).collect();
+        assert_eq!(offsets, [Some(-40), Some(70), Some(-100)]);
+        assert!(judge.finished());
+    }
+
+    #[test]
+    fn presses_outside_the_windows() {
+        let mut judge = Judge::new(&[tap("a", 1000), hold("s", 2000, 2500)], 5.0, 0);
+        // Too early for anything, and a key with no note: ignored.
+        judge.press("a", 800);
+        judge.press("d", 1000);
+        assert!(judge.judgements.is_empty());
+
+        // Too late to be good: ignored, the note is left for `advance` to miss.
+        judge.press("a", 1113);
+        assert!(judge.judgements.is_empty());
+
+        // Inside the miss window but before good: the hold is taken and missed, tail too.
+        judge.press("s", 1850);
+        assert_eq!(grades(&judge), [(1, false, Grade::Miss), (1, true, Grade::Miss)]);
+    }
+
+    #[test]
+    fn hold_releases() {
+        let notes = [hold("a", 1000, 2000), hold("s", 3000, 4000), hold("d", 5000, 6000)];
+        let mut judge = Judge::new(&notes, 5.0, 0);
+
+        // Let go well before the end: the hold breaks.
+        judge.press("a", 1000);
+        judge.release("a", 1500);
+        // Let go just inside the end's window.
+        judge.press("s", 3000);
+        judge.release("s", 3920);
+        // Let go long after the end: still fine.
+        judge.press("d", 5000);
+        judge.release("d", 6500);
+
+        assert_eq!(
+            grades(&judge),
+            [
+                (0, false, Grade::Perfect),
+                (0, true, Grade::Miss),
+                (1, false, Grade::Perfect),
+                (1, true, Grade::Great),
+                (2, false, Grade::Perfect),
+                (2, true, Grade::Perfect),
+            ]
+        );
+    }
+
+    #[test]
+    fn advance_misses_unpressed_notes_and_ends_held_holds() {
+        let mut judge = Judge::new(&[tap("a", 1000), hold("s", 1000, 1500), hold("d", 1200, 1400)], 5.0, 0);
+        judge.press("d", 1200);
+
+        // Still inside the good window: nothing to judge yet.
+        judge.advance(1112);
+        assert_eq!(grades(&judge), [(2, false, Grade::Perfect)]);
+
+        judge.advance(1113);
+        judge.advance(1600);
+        assert_eq!(
+            grades(&judge),
+            [
+                (2, false, Grade::Perfect),
+                (0, false, Grade::Miss),
+                (1, false, Grade::Miss),
+                (1, true, Grade::Miss),
+                (2, true, Grade::Perfect),
+            ]
+        );
+        assert!(judge.finished());
+
+        // Judged notes are not judged again.
+        judge.advance(10_000);
+        assert_eq!(judge.judgements.len(), 5);
+    }
+
+    #[test]
+    fn a_miss_resets_the_combo() {
+        let notes: Vec<_> = (1..=5).map(|i| tap("a", i * 1000)).collect();
+        let mut judge = Judge::new(&notes, 5.0, 0);
+        judge.press("a", 1000);
+        judge.press("a", 2000);
+        judge.press("a", 3000);
+        assert_eq!((judge.combo, judge.max_combo), (3, 3));
+
+        judge.advance(4200);
+        assert_eq!((judge.combo, judge.max_combo), (0, 3));
+
+        judge.press("a", 5000);
+        assert_eq!((judge.combo, judge.max_combo), (1, 3));
+        assert_eq!(judge.count(Grade::Miss), 1);
+        assert!((judge.accuracy() - 0.8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn notes_before_the_start_are_left_out() {
+        let mut judge = Judge::new(&[tap("a", 500), tap("a", 1500)], 5.0, 1000);
+        judge.advance(5000);
+        assert_eq!(grades(&judge), [(0, false, Grade::Miss)]);
+    }
+}
@@ -1,15 +1,11 @@
 use crate::{
+    data::{BeatmapNote, KEYBOARD_LAYOUT},
     editor_state::EditorState,
+    history::Edit,
     timing_util::{meta_timing_points_sorted, snap_time_to_beat_divisor_ms},
 };
 use bevy_egui::egui;
 
-pub const KEYBOARD_LAYOUT: &[&[&str]] = &[
-    &["Q", "W", "E", "R", "T", "Y", "U", "I", "O", "P"],
-    &["A", "S", "D", "F", "G", "H", "J", "K", "L", ";"],
-    &["Z", "X", "C", "V", "B", "N", "M", ",", ".", "/"],
-];
-
 pub fn draw_keyboard(ui: &mut egui::Ui, state: &mut EditorState, keyboard_rect: egui::Rect) {
     ui.allocate_ui_at_rect(keyboard_rect, |ui| {
         ui.set_min_size(keyboard_rect.size());
@@ -38,6 +34,15 @@ pub fn draw_keyboard(ui: &mut egui::Ui, state: &mut EditorState, keyboard_rect:
                 .map(|n| n.key.to_lowercase())
                 .collect();
 
+            // Notes per key for the usage heatmap.
+            let mut key_counts: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+            if state.show_key_heatmap {
+                for n in &state.beatmap.notes {
+                    *key_counts.entry(n.key.to_lowercase()).or_default() += 1;
+                }
+            }
+            let max_count = key_counts.values().copied().max().unwrap_or(0).max(1);
+
             // Scale keys to fill space.
             let row_count = KEYBOARD_LAYOUT.len().max(1) as f32;
             let spacing_y = ui.spacing().item_spacing.y;
@@ -91,11 +96,17 @@ pub fn draw_keyboard(ui: &mut egui::Ui, state: &mut EditorState, keyboard_rect:
                         } else if is_hold_toggled {
                             // Indicates this key has a toggled hold
                             egui::Color32::from_rgb(70, 70, 70)
+                        } else if state.show_key_heatmap {
+                            heat_color(key_counts.get(&key_lower).copied().unwrap_or(0), max_count)
                         } else {
                             egui::Color32::from_rgb(42, 42, 42)
                         });
 
-                        let resp = ui.add_sized(egui::Vec2::new(key_w, key_h), button);
+                        let mut resp = ui.add_sized(egui::Vec2::new(key_w, key_h), button);
+                        if state.show_key_heatmap {
+                            let count = key_counts.get(&key_lower).copied().unwrap_or(0);
+                            resp = resp.on_hover_text(format!("{} notes", count));
+                        }
                         if resp.hovered() {
                             hovered_key = Some(key_lower.clone());
                         }
@@ -103,32 +114,42 @@ pub fn draw_keyboard(ui: &mut egui::Ui, state: &mut EditorState, keyboard_rect:
                         // Right-click deletes a note at the current snapped time.
                         if resp.secondary_clicked() {
                             let timing_points = meta_timing_points_sorted(&state.meta);
-                            let snapped_time = snap_time_to_beat_divisor_ms(&timing_points, state.current_time, 2);
+                            let snapped_time = snap_time_to_beat_divisor_ms(&timing_points, state.current_time, state.beat_divisor);
 
                             // If this key currently has a toggled hold, toggling it off is probably
                             // not what the user intends when deleting; keep the toggle as-is and
                             // only delete committed notes.
-                            let deleted = state.beatmap.delete_note_at(&key_lower, snapped_time);
-                            if deleted {
+                            if let Some(idx) = state.beatmap.find_note_at(&key_lower, snapped_time) {
+                                let note = state.beatmap.notes[idx].clone();
+                                state.apply_edit(Edit::RemoveNotes {
+                                    difficulty: state.selected_difficulty,
+                                    notes: vec![note],
+                                });
                                 state.status = format!("Deleted note: {} @ {}ms", key.to_uppercase(), snapped_time);
                             }
                         }
 
                         if resp.clicked() {
                             let timing_points = meta_timing_points_sorted(&state.meta);
-                            let snapped_time = snap_time_to_beat_divisor_ms(&timing_points, state.current_time, 2);
+                            let snapped_time = snap_time_to_beat_divisor_ms(&timing_points, state.current_time, state.beat_divisor);
 
                             if state.is_hold_mode {
                                 if let Some(start) = state.hold_starts.remove(&key_lower) {
                                     // Add hold note when toggled off
                                     let end = snapped_time.max(start);
-                                    state.beatmap.add_hold_note(key_lower.clone(), start, end);
+                                    state.apply_edit(Edit::AddNotes {
+                                        difficulty: state.selected_difficulty,
+                                        notes: vec![BeatmapNote::hold(key_lower.clone(), start, end)],
+                                    });
                                 } else {
                                     // Remember start time when toggled on
                                     state.hold_starts.insert(key_lower.clone(), snapped_time);
                                 }
                             } else {
-                                state.beatmap.add_tap_note(key_lower, snapped_time);
+                                state.apply_edit(Edit::AddNotes {
+                                    difficulty: state.selected_difficulty,
+                                    notes: vec![BeatmapNote::tap(key_lower, snapped_time)],
+                                });
                             }
                         }
                     }
@@ -139,3 +160,10 @@ pub fn draw_keyboard(ui: &mut egui::Ui, state: &mut EditorState, keyboard_rect:
         });
     });
 }
+
+/// Key fill from dark grey (unused) to orange (the most used key).
+fn heat_color(count: usize, max: usize) -> egui::Color32 {
+    let t = count as f32 / max as f32;
+    let lerp = |a: f32, b: f32| (a + (b - a) * t) as u8;
+    egui::Color32::from_rgb(lerp(42.0, 200.0), lerp(42.0, 100.0), lerp(42.0, 30.0))
+}
@@ -0,0 +1,5 @@
+//! The `.rtm` beatmap data types and the star rating, for tools that rate or inspect maps
+//! without the editor.
+
+pub mod data;
+pub mod star_rating;
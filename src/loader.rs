@@ -1,9 +1,9 @@
-use crate::data::{Beatmap, Meta, MetaDifficulty};
+use crate::data::{Beatmap, Meta, MetaDifficulty, TimingPoint};
 use anyhow::{anyhow, Context, Result};
 use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
 use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use zip::write::FileOptions;
 use zip::{CompressionMethod, ZipArchive, ZipWriter};
 
@@ -103,7 +103,89 @@ pub fn load_rtm(path: &Path) -> Result<RtmPackage> {
     })
 }
 
+/// How `save_rtm_with` treats the file it replaces.
+#[derive(Debug, Clone)]
+pub struct SaveOptions {
+    /// Number of rotating backups of the previous file to keep (`map.1.rtm.bak` is the newest).
+    /// 0 disables backups.
+    pub backups: usize,
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
+        SaveOptions { backups: 3 }
+    }
+}
+
+/// `save_rtm_with` the default options.
 pub fn save_rtm(path: &Path, package: &RtmPackage) -> Result<()> {
+    save_rtm_with(path, package, &SaveOptions::default())
+}
+
+/// Saves atomically: the package is written and synced to a temporary file in the same
+/// directory, which is then renamed over `path`. If anything fails, `path` is left untouched.
+pub fn save_rtm_with(path: &Path, package: &RtmPackage, options: &SaveOptions) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| anyhow!("invalid rtm path: {}", path.display()))?;
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    let tmp_path = dir.join(format!(".{}.tmp", file_name));
+
+    if let Err(err) = write_rtm(&tmp_path, package) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(err);
+    }
+
+    if options.backups > 0 && path.is_file() {
+        if let Err(err) = rotate_backups(path, options.backups) {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(err);
+        }
+    }
+
+    if let Err(err) = fs::rename(&tmp_path, path) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(err).with_context(|| format!("replace rtm: {}", path.display()));
+    }
+
+    // Persist the rename itself. Directories can't be opened for syncing on every
+    // platform, so this is best effort.
+    if let Ok(d) = fs::File::open(dir) {
+        let _ = d.sync_all();
+    }
+    Ok(())
+}
+
+fn backup_path(path: &Path, n: usize) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("map");
+    path.with_file_name(format!("{}.{}.rtm.bak", stem, n))
+}
+
+/// Shifts `map.1.rtm.bak` .. `map.{keep-1}.rtm.bak` up by one, dropping the oldest, and
+/// copies the current file to `map.1.rtm.bak`.
+fn rotate_backups(path: &Path, keep: usize) -> Result<()> {
+    let _ = fs::remove_file(backup_path(path, keep));
+    for n in (1..keep).rev() {
+        let from = backup_path(path, n);
+        if from.is_file() {
+            fs::rename(&from, backup_path(path, n + 1))
+                .with_context(|| format!("rotate backup: {}", from.display()))?;
+        }
+    }
+    let newest = backup_path(path, 1);
+    fs::copy(path, &newest).with_context(|| format!("write backup: {}", newest.display()))?;
+    Ok(())
+}
+
+/// Writes the zip to `path`, which must not be the user's file.
+fn write_rtm(path: &Path, package: &RtmPackage) -> Result<()> {
     let file = fs::File::create(path).with_context(|| format!("create rtm: {}", path.display()))?;
     let mut zip = ZipWriter::new(file);
     let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
@@ -124,7 +206,11 @@ pub fn save_rtm(path: &Path, package: &RtmPackage) -> Result<()> {
     let mut diff_names: BTreeSet<String> = BTreeSet::new();
     for d in &package.difficulties {
         let name = d.meta.filename.clone();
-        diff_names.insert(name.clone());
+        // Zip would happily write a second entry of the same name, leaving a package whose
+        // files depend on which entry a reader picks.
+        if name == "meta.json" || !diff_names.insert(name.clone()) {
+            return Err(anyhow!("two files named {} in the package", name));
+        }
         let json = serde_json::to_vec_pretty(&d.beatmap)
             .with_context(|| format!("serialize difficulty: {}", name))?;
         zip.start_file(&name, options)
@@ -143,10 +229,307 @@ pub fn save_rtm(path: &Path, package: &RtmPackage) -> Result<()> {
             .with_context(|| format!("write other bytes: {}", name))?;
     }
 
-    zip.finish().context("finalize zip")?;
+    let file = zip.finish().context("finalize zip")?;
+    file.sync_all().context("sync rtm")?;
     Ok(())
 }
 
+pub fn new_rtm_package(audio_path: &Path) -> Result<RtmPackage> {
+    let audio_bytes =
+        fs::read(audio_path).with_context(|| format!("read audio: {}", audio_path.display()))?;
+    let audio_name = audio_path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| anyhow!("invalid audio file name: {}", audio_path.display()))?
+        .to_string();
+    let song_name = audio_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("New Song")
+        .to_string();
+
+    let bpm = 120.0;
+    let diff_meta = MetaDifficulty {
+        name: "Normal".to_string(),
+        filename: "normal.json".to_string(),
+    };
+
+    let meta = Meta {
+        songName: song_name,
+        audioFile: audio_name.clone(),
+        timingPoints: vec![TimingPoint {
+            id: 0.0,
+            time: 0.0,
+            bpm,
+            offset: 0,
+            timeSignature: [4, 4],
+        }],
+        bpm,
+        offset: 0,
+        difficulties: vec![diff_meta.clone()],
+        ..Meta::default()
+    };
+
+    let beatmap = Beatmap {
+        name: diff_meta.name.clone(),
+        // A fresh package has no background image yet.
+        bgFile: String::new(),
+        ..Beatmap::new()
+    };
+
+    let mut other_files = BTreeMap::new();
+    other_files.insert(audio_name, audio_bytes);
+
+    Ok(RtmPackage {
+        meta,
+        difficulties: vec![RtmDifficulty {
+            meta: diff_meta,
+            beatmap,
+        }],
+        other_files,
+    })
+}
+
+/// The package entry for a file named in the meta or a beatmap, matching by base name if
+/// the folder differs.
+pub fn find_entry_name(pkg: &RtmPackage, file: &str) -> Option<String> {
+    resolve_entry_name(pkg.other_files.keys().map(String::as_str), file)
+}
+
+/// Like [`find_entry_name`], over a list of entry names.
+pub fn resolve_entry_name<'a>(entries: impl Iterator<Item = &'a str> + Clone, file: &str) -> Option<String> {
+    let wanted = file.trim().replace('\\', "/");
+    if wanted.is_empty() {
+        return None;
+    }
+
+    if entries.clone().any(|name| name == wanted) {
+        return Some(wanted);
+    }
+
+    let wanted_base = wanted.rsplit('/').next().unwrap_or(&wanted);
+    entries
+        .into_iter()
+        .find(|name| {
+            let n = name.replace('\\', "/");
+            n.rsplit('/').next().unwrap_or(&n) == wanted_base
+        })
+        .map(str::to_string)
+}
+
 pub fn import_difficulty_json(path: &Path) -> Result<Beatmap> {
     load_beatmap(path)
 }
+
+/// Whether a difficulty can't be saved as `filename` because `meta.json` or one of the
+/// package's other files already uses that name.
+pub fn is_reserved_filename(other_files: &BTreeMap<String, Vec<u8>>, filename: &str) -> bool {
+    filename == "meta.json" || other_files.contains_key(filename)
+}
+
+/// Names a difficulty file can't take: `meta.json`, the other files, and the files of every
+/// difficulty but `skip`.
+fn used_filenames<'a>(
+    difficulties: &'a [RtmDifficulty],
+    other_files: &'a BTreeMap<String, Vec<u8>>,
+    skip: Option<usize>,
+) -> BTreeSet<&'a str> {
+    let difficulty_files = difficulties
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| Some(*i) != skip)
+        .map(|(_, d)| d.meta.filename.as_str());
+    difficulty_files
+        .chain(other_files.keys().map(String::as_str))
+        .chain(["meta.json"])
+        .collect()
+}
+
+/// Returns `filename`, or `<stem>-N.json` if a difficulty, `meta.json` or one of the other files
+/// already uses it.
+pub fn unique_difficulty_filename(
+    difficulties: &[RtmDifficulty],
+    other_files: &BTreeMap<String, Vec<u8>>,
+    filename: &str,
+) -> String {
+    unique_filename(&used_filenames(difficulties, other_files, None), filename)
+}
+
+fn unique_filename(used: &BTreeSet<&str>, filename: &str) -> String {
+    if !used.contains(filename) {
+        return filename.to_string();
+    }
+
+    let stem = filename.strip_suffix(".json").unwrap_or(filename);
+    let mut n = 2;
+    loop {
+        let candidate = format!("{}-{}.json", stem, n);
+        if !used.contains(candidate.as_str()) {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+/// File name for a difficulty called `name`: lowercase, with whitespace and characters that
+/// aren't allowed in file names replaced by `_`.
+pub fn difficulty_filename(name: &str) -> String {
+    let safe: String = name
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '\\' | '/' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_whitespace() => '_',
+            c => c,
+        })
+        .collect();
+    if safe.is_empty() {
+        "difficulty.json".to_string()
+    } else {
+        format!("{}.json", safe.to_lowercase())
+    }
+}
+
+/// Renames difficulty `idx`, moving its beatmap name and file name along with it.
+pub fn rename_difficulty(
+    difficulties: &mut [RtmDifficulty],
+    other_files: &BTreeMap<String, Vec<u8>>,
+    idx: usize,
+    name: &str,
+) {
+    let used = used_filenames(difficulties, other_files, Some(idx));
+    let filename = unique_filename(&used, &difficulty_filename(name));
+    if let Some(d) = difficulties.get_mut(idx) {
+        d.meta.name = name.to_string();
+        d.meta.filename = filename;
+        d.beatmap.name = name.to_string();
+    }
+}
+
+/// Display name for an imported difficulty: the beatmap's own name unless it is a placeholder.
+pub fn imported_difficulty_name(beatmap: &Beatmap, fallback: &str) -> String {
+    if !beatmap.name.trim().is_empty() && beatmap.name != "New Beatmap" {
+        beatmap.name.clone()
+    } else {
+        fallback.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory of its own for each test.
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rte-loader-{}-{}", std::process::id(), test));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn package(song: &str) -> RtmPackage {
+        RtmPackage {
+            meta: Meta {
+                songName: song.to_string(),
+                ..Meta::default()
+            },
+            difficulties: vec![RtmDifficulty {
+                meta: MetaDifficulty {
+                    name: "Easy".to_string(),
+                    filename: "easy.json".to_string(),
+                },
+                beatmap: Beatmap::new(),
+            }],
+            other_files: BTreeMap::new(),
+        }
+    }
+
+    fn song_of(path: &Path) -> String {
+        load_rtm(path).unwrap().meta.songName
+    }
+
+    #[test]
+    fn save_replaces_the_file_and_leaves_no_temporary() {
+        let dir = temp_dir("replace");
+        let path = dir.join("map.rtm");
+        save_rtm_with(&path, &package("first"), &SaveOptions { backups: 0 }).unwrap();
+        save_rtm_with(&path, &package("second"), &SaveOptions { backups: 0 }).unwrap();
+
+        assert_eq!(song_of(&path), "second");
+        let entries: Vec<_> = fs::read_dir(&dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(entries, ["map.rtm"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failed_write_leaves_the_original_untouched() {
+        let dir = temp_dir("failed_write");
+        let path = dir.join("map.rtm");
+        save_rtm_with(&path, &package("original"), &SaveOptions::default()).unwrap();
+        let original = fs::read(&path).unwrap();
+
+        // A directory where the temporary file goes makes creating it fail.
+        fs::create_dir(dir.join(".map.rtm.tmp")).unwrap();
+        assert!(save_rtm_with(&path, &package("changed"), &SaveOptions::default()).is_err());
+
+        assert_eq!(fs::read(&path).unwrap(), original);
+        assert!(!backup_path(&path, 1).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failing_partway_through_the_zip_leaves_the_original_untouched() {
+        let dir = temp_dir("failed_partway");
+        let path = dir.join("map.rtm");
+        save_rtm(&path, &package("original")).unwrap();
+        let original = fs::read(&path).unwrap();
+
+        // meta.json and the first difficulty are written before the clash is found.
+        let mut pkg = package("changed");
+        pkg.difficulties.push(pkg.difficulties[0].clone());
+        assert!(save_rtm(&path, &pkg).is_err());
+
+        assert_eq!(fs::read(&path).unwrap(), original);
+        let entries: Vec<_> = fs::read_dir(&dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(entries, ["map.rtm"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn backups_rotate_and_keep_only_the_newest() {
+        let dir = temp_dir("backups");
+        let path = dir.join("map.rtm");
+        let options = SaveOptions { backups: 2 };
+        for song in ["v1", "v2", "v3", "v4"] {
+            save_rtm_with(&path, &package(song), &options).unwrap();
+        }
+
+        assert_eq!(song_of(&path), "v4");
+        assert_eq!(song_of(&backup_path(&path, 1)), "v3");
+        assert_eq!(song_of(&backup_path(&path, 2)), "v2");
+        assert!(!backup_path(&path, 3).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn difficulty_filenames_avoid_meta_and_other_files() {
+        let mut pkg = package("song");
+        pkg.other_files.insert("hard.json".to_string(), Vec::new());
+        let unique = |pkg: &RtmPackage, name: &str| {
+            unique_difficulty_filename(&pkg.difficulties, &pkg.other_files, &difficulty_filename(name))
+        };
+        assert_eq!(unique(&pkg, "Easy"), "easy-2.json");
+        assert_eq!(unique(&pkg, "Hard"), "hard-2.json");
+        assert_eq!(unique(&pkg, "Meta"), "meta-2.json");
+        assert_eq!(unique(&pkg, "Normal"), "normal.json");
+
+        let other_files = pkg.other_files.clone();
+        rename_difficulty(&mut pkg.difficulties, &other_files, 0, "Meta");
+        assert_eq!(pkg.difficulties[0].meta.filename, "meta-2.json");
+        rename_difficulty(&mut pkg.difficulties, &other_files, 0, "Hard");
+        assert_eq!(pkg.difficulties[0].meta.filename, "hard-2.json");
+        // Keeping its own name is not a clash.
+        rename_difficulty(&mut pkg.difficulties, &other_files, 0, "Hard");
+        assert_eq!(pkg.difficulties[0].meta.filename, "hard-2.json");
+    }
+}
@@ -1,18 +1,47 @@
-mod data;
+mod autosave;
+mod background;
+mod cli;
+mod difficulty_ui;
 mod loader;
+mod map_stats;
+mod meta_ui;
+mod judgement;
+mod metronome;
 mod editor_state;
+mod history;
+mod hitsound_audio;
+mod hitsound_ui;
+mod hitsound_util;
 mod timing_util;
 mod audio_util;
 mod timeline_ui;
+mod timing_ui;
 mod keyboard_ui;
 mod ui;
+mod sample_ui;
+mod playtest;
+mod rating_ui;
+mod record;
+mod selection;
+mod stats_ui;
+mod suggestions;
+mod tempo;
+mod validation;
+mod waveform;
+
+use rhythm_typer_editor::{data, star_rating};
 
 use bevy::prelude::*;
 use bevy::asset::AssetPlugin;
-use bevy_kira_audio::AudioPlugin as KiraAudioPlugin;
+use bevy_kira_audio::{AudioApp, AudioPlugin as KiraAudioPlugin};
 use editor_state::EditorState;
 
 fn main() {
+    // Subcommands run headless and exit before any Bevy/egui setup.
+    if let Some(code) = cli::run(std::env::args().skip(1).collect()) {
+        std::process::exit(code);
+    }
+
     App::new()
         .add_plugins(
             DefaultPlugins
@@ -21,14 +50,62 @@ fn main() {
                     file_path: ".".to_string(),
                     ..default()
                 })
+                // Closing the window goes through autosave::close_requested_system so
+                // unsaved changes can be confirmed first.
+                .set(WindowPlugin {
+                    close_when_requested: false,
+                    ..default()
+                })
                 // We use bevy_kira_audio for playback/seek so we disable Bevy's built-in audio plugin
                 // to avoid duplicate AudioSource asset loader warnings.
                 .disable::<bevy::audio::AudioPlugin>(),
         )
         .add_plugins(KiraAudioPlugin)
+        .add_audio_channel::<hitsound_audio::HitsoundChannel>()
+        .add_audio_channel::<metronome::MetronomeChannel>()
         .add_plugins(bevy_egui::EguiPlugin)
         .init_resource::<EditorState>()
-        .add_systems(Startup, ui::setup)
-        .add_systems(Update, ui::ui_system)
+        .init_resource::<autosave::Autosaver>()
+        .init_resource::<hitsound_audio::HitsoundBank>()
+        .init_resource::<hitsound_audio::HitsoundPlayback>()
+        .init_resource::<metronome::Metronome>()
+        .init_resource::<waveform::WaveformLoader>()
+        .init_resource::<tempo::TimingDetector>()
+        .init_resource::<suggestions::OnsetLoader>()
+        .init_resource::<background::BackgroundLoader>()
+        .add_systems(
+            Startup,
+            (
+                ui::setup,
+                autosave::check_recovery,
+                hitsound_audio::setup_hitsounds,
+                metronome::setup_metronome,
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                ui::ui_system,
+                sample_ui::sample_window_system.after(ui::ui_system),
+                stats_ui::stats_window_system.after(ui::ui_system),
+                difficulty_ui::difficulty_window_system.after(ui::ui_system),
+                background::background_window_system.after(ui::ui_system),
+                background::background_system.before(ui::ui_system),
+                autosave::autosave_system,
+                autosave::close_requested_system,
+                (
+                    hitsound_audio::refresh_custom_hitsounds,
+                    hitsound_audio::hitsound_playback_system,
+                )
+                    .chain()
+                    .after(ui::ui_system),
+                metronome::metronome_system.after(ui::ui_system),
+                waveform::waveform_system.before(ui::ui_system),
+                tempo::timing_detect_system.after(ui::ui_system),
+                suggestions::onset_system.before(ui::ui_system),
+                record::record_system.after(ui::ui_system),
+                playtest::playtest_system.after(ui::ui_system),
+            ),
+        )
         .run();
 }
\ No newline at end of file
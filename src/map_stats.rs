@@ -0,0 +1,126 @@
+use crate::data::BeatmapNote;
+use std::collections::BTreeMap;
+
+/// Notes closer together than this count as one chord.
+const CHORD_MS: u32 = 5;
+/// Chords at most this far apart belong to the same stream (a 1/4 stream at 100 BPM).
+const STREAM_GAP_MS: u32 = 150;
+const NPS_BUCKET_MS: u32 = 1000;
+
+/// Counts and densities for one difficulty.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MapStats {
+    pub taps: usize,
+    pub holds: usize,
+    /// From the first note's start to the last note's end.
+    pub drain_ms: u32,
+    pub avg_nps: f32,
+    /// The most notes starting within any one-second window.
+    pub peak_nps: usize,
+    /// Notes starting in each second of the map, from 0ms.
+    pub nps_curve: Vec<usize>,
+    /// Notes in the longest run of chords no more than `STREAM_GAP_MS` apart.
+    pub longest_stream: usize,
+    pub longest_stream_start_ms: u32,
+    /// Number of chords of each size; single notes are size 1.
+    pub chord_sizes: BTreeMap<usize, usize>,
+}
+
+pub fn map_stats(notes: &[BeatmapNote]) -> MapStats {
+    let mut stats = MapStats::default();
+    if notes.is_empty() {
+        return stats;
+    }
+
+    let mut starts: Vec<u32> = notes.iter().map(|n| n.get_start_time()).collect();
+    starts.sort_unstable();
+    stats.holds = notes.iter().filter(|n| n.note_type == "hold").count();
+    stats.taps = notes.len() - stats.holds;
+
+    let first = starts[0];
+    let last = notes.iter().map(|n| n.get_end_time()).max().unwrap_or(first);
+    stats.drain_ms = last - first;
+    stats.avg_nps = notes.len() as f32 / (stats.drain_ms.max(NPS_BUCKET_MS) as f32 / 1000.0);
+
+    stats.nps_curve = vec![0; (starts[starts.len() - 1] / NPS_BUCKET_MS) as usize + 1];
+    for &t in &starts {
+        stats.nps_curve[(t / NPS_BUCKET_MS) as usize] += 1;
+    }
+    let mut lo = 0;
+    for hi in 0..starts.len() {
+        while starts[hi] - starts[lo] >= NPS_BUCKET_MS {
+            lo += 1;
+        }
+        stats.peak_nps = stats.peak_nps.max(hi - lo + 1);
+    }
+
+    // Group into chords as (time, size), then find the longest run of close chords.
+    let mut chords: Vec<(u32, usize)> = Vec::new();
+    for &t in &starts {
+        match chords.last_mut() {
+            Some((time, size)) if t - *time <= CHORD_MS => *size += 1,
+            _ => chords.push((t, 1)),
+        }
+    }
+    for &(_, size) in &chords {
+        *stats.chord_sizes.entry(size).or_default() += 1;
+    }
+    let mut run_start = 0;
+    let mut run_notes = 0;
+    for i in 0..chords.len() {
+        if i > 0 && chords[i].0 - chords[i - 1].0 > STREAM_GAP_MS {
+            run_start = i;
+            run_notes = 0;
+        }
+        run_notes += chords[i].1;
+        // A lone chord isn't a stream.
+        if i > run_start && run_notes > stats.longest_stream {
+            stats.longest_stream = run_notes;
+            stats.longest_stream_start_ms = chords[run_start].0;
+        }
+    }
+
+    stats
+}
+
+/// `ms` as `m:ss`.
+pub fn format_duration(ms: u32) -> String {
+    let s = ms / 1000;
+    format!("{}:{:02}", s / 60, s % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn taps(times: &[u32]) -> Vec<BeatmapNote> {
+        times.iter().map(|&t| BeatmapNote::tap("a".to_string(), t)).collect()
+    }
+
+    #[test]
+    fn an_empty_map_has_no_stats() {
+        assert_eq!(map_stats(&[]), MapStats::default());
+    }
+
+    #[test]
+    fn a_single_chord_is_not_a_stream() {
+        let stats = map_stats(&taps(&[100, 102, 100 + CHORD_MS]));
+        assert_eq!(stats.chord_sizes, BTreeMap::from([(3, 1)]));
+        assert_eq!(stats.longest_stream, 0);
+        assert_eq!(stats.peak_nps, 3);
+    }
+
+    #[test]
+    fn peak_nps_window_excludes_a_note_a_full_second_later() {
+        assert_eq!(map_stats(&taps(&[0, NPS_BUCKET_MS - 1])).peak_nps, 2);
+        assert_eq!(map_stats(&taps(&[0, NPS_BUCKET_MS])).peak_nps, 1);
+    }
+
+    #[test]
+    fn a_gap_just_over_the_stream_gap_breaks_the_stream() {
+        let gap = STREAM_GAP_MS;
+        let stats = map_stats(&taps(&[1000, 1000 + gap, 1000 + 2 * gap, 1001 + 3 * gap, 1001 + 4 * gap]));
+        assert_eq!(stats.longest_stream, 3);
+        assert_eq!(stats.longest_stream_start_ms, 1000);
+    }
+}
@@ -0,0 +1,245 @@
+use crate::{editor_state::EditorState, loader};
+use bevy_egui::egui;
+
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "ogg", "wav", "flac"];
+pub const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg"];
+const VIDEO_EXTENSIONS: &[&str] = &["mp4", "webm", "mkv", "avi", "mov"];
+
+/// Offered by the language picker; any other value in `meta.json` is kept as it is.
+const LANGUAGES: &[&str] = &[
+    "English",
+    "Japanese",
+    "Chinese",
+    "Korean",
+    "French",
+    "German",
+    "Spanish",
+    "Italian",
+    "Portuguese",
+    "Russian",
+    "Polish",
+    "Swedish",
+    "Instrumental",
+    "Other",
+];
+
+pub fn has_extension(name: &str, extensions: &[&str]) -> bool {
+    name.rsplit_once('.')
+        .is_some_and(|(_, ext)| extensions.iter().any(|e| e.eq_ignore_ascii_case(ext)))
+}
+
+/// Picks `value` from the package files with one of `extensions`, and warns when `value`
+/// names a file the package doesn't contain. Returns true if `value` changed.
+fn package_file_picker(
+    ui: &mut egui::Ui,
+    id: &str,
+    value: &mut String,
+    files: Option<&[String]>,
+    extensions: &[&str],
+) -> bool {
+    let mut changed = false;
+    let shown = if value.is_empty() { "<none>" } else { value.as_str() }.to_string();
+    egui::ComboBox::from_id_source(id)
+        .selected_text(shown)
+        .width(ui.available_width() - 8.0)
+        .show_ui(ui, |ui| {
+            let matching = files.unwrap_or_default().iter().filter(|f| has_extension(f, extensions));
+            for f in matching {
+                if ui.selectable_label(value == f, f).clicked() && value != f {
+                    *value = f.clone();
+                    changed = true;
+                }
+            }
+        });
+    if let Some(files) = files {
+        // Resolved the way the editor finds files, which also matches by base name.
+        let found = loader::resolve_entry_name(files.iter().map(String::as_str), value).is_some();
+        if !value.is_empty() && !found {
+            ui.colored_label(egui::Color32::from_rgb(230, 170, 60), "⚠ Not in the package");
+        }
+    }
+    changed
+}
+
+/// A time field with a button that sets it to the playhead. Returns true if `value` changed.
+fn time_with_playhead(
+    ui: &mut egui::Ui,
+    state: &mut EditorState,
+    value: impl Fn(&mut EditorState) -> &mut i64,
+) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        changed |= ui
+            .add(egui::DragValue::new(value(state)).speed(10).suffix(" ms"))
+            .changed();
+        if ui.button("⏱ Set to playhead").clicked() {
+            *value(state) = state.current_time as i64;
+            state.history.seal();
+            changed = true;
+        }
+        if ui.small_button("▶").on_hover_text("Seek here").clicked() {
+            let ms = (*value(state)).max(0) as u32;
+            state.current_time = ms;
+            state.audio_seek_request = Some(ms);
+        }
+    });
+    changed
+}
+
+/// Tags are stored space-separated; shows them as a list with an entry to add more.
+fn draw_tags(ui: &mut egui::Ui, state: &mut EditorState, new_tag: &mut String) -> bool {
+    let mut tags: Vec<String> = state.meta.tags.split_whitespace().map(str::to_string).collect();
+    let mut remove: Option<usize> = None;
+
+    ui.horizontal_wrapped(|ui| {
+        for (i, tag) in tags.iter().enumerate() {
+            if ui.small_button(format!("{} ✖", tag)).on_hover_text("Remove tag").clicked() {
+                remove = Some(i);
+            }
+        }
+        if tags.is_empty() {
+            ui.weak("No tags");
+        }
+    });
+
+    let mut add = false;
+    ui.horizontal(|ui| {
+        let resp = ui.add(egui::TextEdit::singleline(new_tag).hint_text("Add tags").desired_width(160.0));
+        add |= resp.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+        add |= ui.button("➕").clicked();
+    });
+
+    let mut changed = false;
+    if let Some(i) = remove {
+        tags.remove(i);
+        changed = true;
+    }
+    if add {
+        // Commas are accepted too, since tags can't contain spaces anyway.
+        for tag in new_tag.split(|c: char| c.is_whitespace() || c == ',').filter(|t| !t.is_empty()) {
+            if !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                tags.push(tag.to_string());
+                changed = true;
+            }
+        }
+        new_tag.clear();
+    }
+    if changed {
+        state.meta.tags = tags.join(" ");
+        state.history.seal();
+    }
+    changed
+}
+
+/// Every `meta.json` field except timing and difficulties. Returns true if anything changed.
+pub fn draw_meta(ui: &mut egui::Ui, state: &mut EditorState, audio_len_ms: Option<u32>) -> bool {
+    let mut changed = false;
+    let files: Option<Vec<String>> =
+        state.rtm_package.as_ref().map(|p| p.other_files.keys().cloned().collect());
+    let files = files.as_deref();
+
+    ui.heading("Meta (meta.json)");
+
+    ui.label("Song Name");
+    changed |= ui.text_edit_singleline(&mut state.meta.songName).changed();
+
+    ui.label("Artist Name");
+    changed |= ui.text_edit_singleline(&mut state.meta.artistName).changed();
+
+    ui.label("Mapper");
+    changed |= ui.text_edit_singleline(&mut state.meta.mapper).changed();
+
+    ui.label("Description");
+    changed |= ui
+        .add(egui::TextEdit::multiline(&mut state.meta.description).desired_rows(3))
+        .changed();
+
+    ui.label("Tags");
+    let mut new_tag = std::mem::take(&mut state.meta_new_tag);
+    changed |= draw_tags(ui, state, &mut new_tag);
+    state.meta_new_tag = new_tag;
+
+    ui.label("Language");
+    let shown = if state.meta.language.is_empty() {
+        "<unspecified>".to_string()
+    } else {
+        state.meta.language.clone()
+    };
+    let before = state.meta.language.clone();
+    egui::ComboBox::from_id_source("meta_language")
+        .selected_text(shown)
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut state.meta.language, String::new(), "<unspecified>");
+            for lang in LANGUAGES {
+                ui.selectable_value(&mut state.meta.language, lang.to_string(), *lang);
+            }
+        });
+    if state.meta.language != before {
+        state.history.seal();
+        changed = true;
+    }
+
+    changed |= ui.checkbox(&mut state.meta.explicit, "Explicit content").changed();
+
+    ui.label("Audio File");
+    changed |= package_file_picker(ui, "meta_audio", &mut state.meta.audioFile, files, AUDIO_EXTENSIONS);
+    if state.meta.audioFile.is_empty() {
+        ui.colored_label(egui::Color32::from_rgb(230, 80, 80), "⚠ The mapset needs an audio file");
+    }
+
+    ui.label("Preview Time");
+    changed |= time_with_playhead(ui, state, |s| &mut s.meta.previewTime);
+    let preview = state.meta.previewTime;
+    if preview < 0 || audio_len_ms.is_some_and(|len| preview > len as i64) {
+        ui.colored_label(egui::Color32::from_rgb(230, 170, 60), "⚠ Outside the song");
+    }
+
+    ui.label("Background Files");
+    let mut remove_bg: Option<usize> = None;
+    for i in 0..state.meta.backgroundFiles.len() {
+        ui.horizontal(|ui| {
+            if ui.small_button("✖").on_hover_text("Remove").clicked() {
+                remove_bg = Some(i);
+            }
+            let id = format!("meta_bg_{}", i);
+            ui.vertical(|ui| {
+                let bg = &mut state.meta.backgroundFiles[i];
+                changed |= package_file_picker(ui, &id, bg, files, IMAGE_EXTENSIONS);
+            });
+        });
+    }
+    if let Some(i) = remove_bg {
+        state.meta.backgroundFiles.remove(i);
+        state.history.seal();
+        changed = true;
+    }
+    if ui.button("➕ Add background").clicked() {
+        // Start from the first image in the package that isn't listed yet.
+        let next = files
+            .unwrap_or_default()
+            .iter()
+            .find(|f| has_extension(f, IMAGE_EXTENSIONS) && !state.meta.backgroundFiles.contains(f))
+            .cloned()
+            .unwrap_or_default();
+        state.meta.backgroundFiles.push(next);
+        state.history.seal();
+        changed = true;
+    }
+
+    ui.label("Video File");
+    let mut has_video = state.meta.videoFile.is_some();
+    if ui.checkbox(&mut has_video, "Has a video").changed() {
+        state.meta.videoFile = has_video.then(String::new);
+        state.history.seal();
+        changed = true;
+    }
+    if let Some(mut video) = state.meta.videoFile.take() {
+        changed |= package_file_picker(ui, "meta_video", &mut video, files, VIDEO_EXTENSIONS);
+        state.meta.videoFile = Some(video);
+
+        ui.label("Video Start Time");
+        changed |= time_with_playhead(ui, state, |s| &mut s.meta.videoStartTime);
+    }
+
+    changed
+}
@@ -0,0 +1,57 @@
+use crate::{
+    audio_util,
+    editor_state::EditorState,
+    timing_util::{beats_in_range, meta_timing_points_sorted},
+};
+use bevy::prelude::*;
+use bevy_kira_audio::{AudioChannel, AudioControl, AudioSource};
+use std::f32::consts::TAU;
+
+#[derive(Resource)]
+pub struct MetronomeChannel;
+
+#[derive(Resource, Default)]
+pub struct Metronome {
+    accent: Handle<AudioSource>,
+    click: Handle<AudioSource>,
+    last_ms: Option<u32>,
+}
+
+fn click_sound(freq: f32) -> AudioSource {
+    AudioSource {
+        sound: audio_util::synth_sound(0.04, |t| (TAU * freq * t).sin() * (-t / 0.008).exp() * 0.8),
+    }
+}
+
+pub fn setup_metronome(mut sources: ResMut<Assets<AudioSource>>, mut metronome: ResMut<Metronome>) {
+    metronome.accent = sources.add(click_sound(1600.0));
+    metronome.click = sources.add(click_sound(1000.0));
+}
+
+/// Clicks on every beat the playhead passes, with a higher click on each downbeat.
+pub fn metronome_system(
+    state: Res<EditorState>,
+    mut metronome: ResMut<Metronome>,
+    channel: Res<AudioChannel<MetronomeChannel>>,
+) {
+    let window = audio_util::playback_window(&mut metronome.last_ms, &state);
+    let Some((prev, now)) = window.filter(|_| state.metronome_enabled) else {
+        return;
+    };
+    if now == prev {
+        return;
+    }
+
+    let points = meta_timing_points_sorted(&state.meta);
+    // Only the last beat matters if a slow frame covered several.
+    if let Some(&(_, downbeat)) = beats_in_range(&points, prev, now).last() {
+        let sound = if downbeat {
+            metronome.accent.clone()
+        } else {
+            metronome.click.clone()
+        };
+        channel
+            .play(sound)
+            .with_volume(state.metronome_volume as f64);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::{
+    data::KEYBOARD_LAYOUT,
+    editor_state::EditorState,
+    judgement::{Grade, Judge},
+    record::{self, key_code},
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+/// Starts playing from the playhead with key presses judged instead of recorded.
+pub fn start_playtest(state: &mut EditorState) {
+    record::finish_take(state);
+    state.record_mode = false;
+    let start = state.current_time;
+    state.playtest = Some(Judge::new(&state.beatmap.notes, state.beatmap.overallDifficulty, start));
+    state.is_playing = true;
+    state.audio_seek_request = Some(start);
+    state.status = format!("Playtest from {}ms (Esc to stop)", start);
+}
+
+pub fn stop_playtest(state: &mut EditorState) {
+    let Some(judge) = state.playtest.take() else {
+        return;
+    };
+    state.status = format!(
+        "Playtest: {:.2}% accuracy, max combo {}, {} misses",
+        judge.accuracy() * 100.0,
+        judge.max_combo,
+        judge.count(Grade::Miss)
+    );
+}
+
+fn grade_color(grade: Grade) -> egui::Color32 {
+    match grade {
+        Grade::Perfect => egui::Color32::from_rgb(120, 200, 255),
+        Grade::Great => egui::Color32::from_rgb(120, 230, 120),
+        Grade::Good => egui::Color32::from_rgb(230, 200, 90),
+        Grade::Miss => egui::Color32::from_rgb(230, 80, 80),
+    }
+}
+
+/// Feeds physical key input to the playtest judge and shows combo and accuracy. The
+/// playtest ends when playback stops; seeking restarts it from the new time.
+pub fn playtest_system(
+    mut contexts: EguiContexts,
+    mut state: ResMut<EditorState>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+) {
+    if state.playtest.is_none() {
+        return;
+    }
+    if keyboard.just_pressed(KeyCode::Escape) {
+        state.is_playing = false;
+    }
+    if !state.is_playing {
+        stop_playtest(&mut state);
+        return;
+    }
+    if state.playhead_jumped {
+        let start = state.current_time;
+        state.playtest = Some(Judge::new(&state.beatmap.notes, state.beatmap.overallDifficulty, start));
+    }
+
+    let ctx = contexts.ctx_mut();
+    let typing = ctx.wants_keyboard_input();
+    let now = (state.current_time as i64 - state.input_latency_ms as i64).max(0) as u32;
+    let state = &mut *state;
+    let Some(judge) = state.playtest.as_mut() else {
+        return;
+    };
+    for key in KEYBOARD_LAYOUT.iter().flat_map(|row| row.iter()) {
+        let Some(code) = key_code(key) else {
+            continue;
+        };
+        if keyboard.just_released(code) {
+            judge.release(key, now);
+        }
+        if keyboard.just_pressed(code) && !typing {
+            judge.press(key, now);
+        }
+    }
+    judge.advance(now);
+
+    let windows = judge.windows();
+    egui::Window::new("🎮 Playtest")
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-280.0, 60.0))
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.heading(format!("{}x", judge.combo));
+            ui.label(format!("Accuracy {:.2}%", judge.accuracy() * 100.0));
+            ui.label(format!("Max combo {}", judge.max_combo));
+            if let Some(last) = judge.judgements.last() {
+                let text = match last.offset_ms {
+                    Some(off) => format!("{} ({:+}ms)", last.grade.label(), off),
+                    None => last.grade.label().to_string(),
+                };
+                ui.colored_label(grade_color(last.grade), text);
+            }
+            ui.separator();
+            for grade in Grade::ALL {
+                ui.colored_label(grade_color(grade), format!("{}: {}", grade.label(), judge.count(grade)));
+            }
+            ui.weak(format!(
+                "Windows ±{:.0} / ±{:.0} / ±{:.0}ms",
+                windows.perfect, windows.great, windows.good
+            ));
+            ui.horizontal(|ui| {
+                ui.label("Input latency (ms)");
+                ui.add(egui::DragValue::new(&mut state.input_latency_ms).speed(1).clamp_range(-500..=500))
+                    .on_hover_text("Shared with Record Mode; raise it if your hits are judged late");
+            });
+            if judge.finished() {
+                ui.label("All notes judged. Esc to stop.");
+            }
+        });
+}
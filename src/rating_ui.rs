@@ -0,0 +1,68 @@
+use crate::{
+    editor_state::EditorState,
+    star_rating::{star_rating, StarRating, SECTION_MS},
+};
+use bevy_egui::egui;
+
+/// The rating of the working beatmap, recomputed only when its notes change.
+pub fn current_rating(state: &mut EditorState) -> StarRating {
+    match &state.star_rating_cache {
+        Some((notes, rating)) if *notes == state.beatmap.notes => rating.clone(),
+        _ => {
+            let rating = star_rating(&state.beatmap.notes);
+            state.star_rating_cache = Some((state.beatmap.notes.clone(), rating.clone()));
+            rating
+        }
+    }
+}
+
+/// Star rating and strain curve of the working difficulty. Clicking the curve seeks.
+pub fn draw_star_rating(ui: &mut egui::Ui, state: &mut EditorState) {
+    ui.heading("Difficulty Rating");
+
+    let rating = current_rating(state);
+    ui.label(egui::RichText::new(format!("{:.2}★", rating.stars)).strong().size(18.0));
+
+    if rating.section_strains.is_empty() {
+        ui.label("No notes yet.");
+        return;
+    }
+
+    let size = egui::vec2(ui.available_width(), 60.0);
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, egui::Color32::from_rgb(20, 20, 20));
+
+    let max = rating.section_strains.iter().copied().fold(0.0, f64::max).max(1e-6);
+    let count = rating.section_strains.len();
+    let x_of = |i: f32| rect.left() + i / count as f32 * rect.width();
+    let points: Vec<egui::Pos2> = rating
+        .section_strains
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            let y = rect.bottom() - (s / max) as f32 * (rect.height() - 4.0);
+            egui::pos2(x_of(i as f32 + 0.5), y)
+        })
+        .collect();
+    painter.add(egui::Shape::line(
+        points,
+        egui::Stroke::new(1.5, egui::Color32::from_rgb(230, 150, 80)),
+    ));
+
+    let playhead_x = x_of(state.current_time as f32 / SECTION_MS as f32);
+    painter.line_segment(
+        [egui::pos2(playhead_x, rect.top()), egui::pos2(playhead_x, rect.bottom())],
+        egui::Stroke::new(1.0, egui::Color32::from_rgb(255, 80, 80)),
+    );
+
+    let response = response.on_hover_text("Strain over the map; click to seek");
+    if response.clicked() {
+        if let Some(pos) = response.interact_pointer_pos() {
+            let frac = ((pos.x - rect.left()) / rect.width()).clamp(0.0, 1.0);
+            let ms = (frac * (count as u32 * SECTION_MS) as f32) as u32;
+            state.current_time = ms;
+            state.audio_seek_request = Some(ms);
+        }
+    }
+}
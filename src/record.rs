@@ -0,0 +1,217 @@
+use crate::{
+    data::{BeatmapNote, KEYBOARD_LAYOUT},
+    editor_state::EditorState,
+    history::Edit,
+    timing_util::{meta_timing_points_sorted, snap_time_to_beat_divisor_ms},
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use std::collections::HashMap;
+
+/// Notes recorded since playback started in record mode. They are in the beatmap already
+/// so they show up while playing, and become one undo step when the take ends.
+#[derive(Debug, Clone, Default)]
+pub struct RecordTake {
+    pub notes: Vec<BeatmapNote>,
+    /// Keys held down: the raw press time (ms) and the tap note placed for it.
+    held: HashMap<String, (u32, BeatmapNote)>,
+}
+
+/// The physical key for a `KEYBOARD_LAYOUT` key.
+pub fn key_code(key: &str) -> Option<KeyCode> {
+    Some(match key {
+        "Q" => KeyCode::KeyQ,
+        "W" => KeyCode::KeyW,
+        "E" => KeyCode::KeyE,
+        "R" => KeyCode::KeyR,
+        "T" => KeyCode::KeyT,
+        "Y" => KeyCode::KeyY,
+        "U" => KeyCode::KeyU,
+        "I" => KeyCode::KeyI,
+        "O" => KeyCode::KeyO,
+        "P" => KeyCode::KeyP,
+        "A" => KeyCode::KeyA,
+        "S" => KeyCode::KeyS,
+        "D" => KeyCode::KeyD,
+        "F" => KeyCode::KeyF,
+        "G" => KeyCode::KeyG,
+        "H" => KeyCode::KeyH,
+        "J" => KeyCode::KeyJ,
+        "K" => KeyCode::KeyK,
+        "L" => KeyCode::KeyL,
+        ";" => KeyCode::Semicolon,
+        "Z" => KeyCode::KeyZ,
+        "X" => KeyCode::KeyX,
+        "C" => KeyCode::KeyC,
+        "V" => KeyCode::KeyV,
+        "B" => KeyCode::KeyB,
+        "N" => KeyCode::KeyN,
+        "M" => KeyCode::KeyM,
+        "," => KeyCode::Comma,
+        "." => KeyCode::Period,
+        "/" => KeyCode::Slash,
+        _ => return None,
+    })
+}
+
+/// Map time of a key event at the playhead, corrected for latency and optionally snapped.
+fn record_time(state: &EditorState, raw_ms: u32) -> u32 {
+    let t = (raw_ms as i64 - state.input_latency_ms as i64).max(0) as u32;
+    if state.record_quantize {
+        let points = meta_timing_points_sorted(&state.meta);
+        snap_time_to_beat_divisor_ms(&points, t, state.beat_divisor)
+    } else {
+        t
+    }
+}
+
+fn press(state: &mut EditorState, key: String) {
+    let now = state.current_time;
+    let time = record_time(state, now);
+    let taken = state
+        .beatmap
+        .notes
+        .iter()
+        .any(|n| n.key == key && n.get_start_time() <= time && time <= n.get_end_time());
+    if taken {
+        return;
+    }
+    let note = BeatmapNote::tap(key.clone(), time);
+    state.beatmap.insert_note(note.clone());
+    if let Some(take) = state.record_take.as_mut() {
+        take.held.insert(key, (now, note));
+    }
+}
+
+/// Turns the key's tap into a hold if it was held for at least `record_hold_ms`.
+fn release(state: &mut EditorState, key: &str) {
+    let now = state.current_time;
+    let Some((pressed_at, tap)) = state.record_take.as_mut().and_then(|t| t.held.remove(key)) else {
+        return;
+    };
+    let start = tap.get_start_time();
+    let end = record_time(state, now);
+    let note = if now.saturating_sub(pressed_at) >= state.record_hold_ms && end > start {
+        state.beatmap.remove_note(&tap);
+        let hold = BeatmapNote::hold(key.to_string(), start, end);
+        state.beatmap.insert_note(hold.clone());
+        hold
+    } else {
+        tap
+    };
+    if let Some(take) = state.record_take.as_mut() {
+        take.notes.push(note);
+    }
+}
+
+/// Ends the take in progress, releasing any keys still held, and records its notes as one
+/// undo step.
+pub fn finish_take(state: &mut EditorState) {
+    let held: Vec<String> = match state.record_take.as_ref() {
+        Some(take) => take.held.keys().cloned().collect(),
+        None => return,
+    };
+    for key in held {
+        release(state, &key);
+    }
+    let Some(take) = state.record_take.take() else {
+        return;
+    };
+    if take.notes.is_empty() {
+        return;
+    }
+
+    let count = take.notes.len();
+    // The beatmap already holds the recorded notes, so record without re-applying.
+    state.history.push(Edit::AddNotes {
+        difficulty: state.selected_difficulty,
+        notes: take.notes,
+    });
+    state.mark_dirty();
+    state.status = format!("Recorded {} notes", count);
+}
+
+/// Removes the take in progress from the beatmap without recording it.
+pub fn discard_take(state: &mut EditorState) {
+    let Some(take) = state.record_take.take() else {
+        return;
+    };
+    let notes: Vec<BeatmapNote> = take
+        .notes
+        .into_iter()
+        .chain(take.held.into_values().map(|(_, n)| n))
+        .collect();
+    state.beatmap.remove_notes(&notes);
+    let count = notes.len();
+    state.status = format!("Discarded take ({} notes)", count);
+}
+
+/// Places notes for physical key presses while playing in record mode. A take starts when
+/// playback starts and ends when it stops or record mode is turned off.
+pub fn record_system(
+    mut contexts: EguiContexts,
+    mut state: ResMut<EditorState>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+) {
+    // A playtest reads the same keys, so it pauses recording.
+    if !state.record_mode || !state.is_playing || state.playtest.is_some() {
+        finish_take(&mut state);
+        return;
+    }
+    if state.record_take.is_none() {
+        state.record_take = Some(RecordTake::default());
+    }
+
+    let typing = contexts.ctx_mut().wants_keyboard_input();
+    let modified = keyboard.any_pressed([
+        KeyCode::ControlLeft,
+        KeyCode::ControlRight,
+        KeyCode::AltLeft,
+        KeyCode::AltRight,
+        KeyCode::SuperLeft,
+        KeyCode::SuperRight,
+    ]);
+
+    for key in KEYBOARD_LAYOUT.iter().flat_map(|row| row.iter()) {
+        let Some(code) = key_code(key) else {
+            continue;
+        };
+        // Keys are stored lowercase, as when placed from the on-screen keyboard.
+        let key = key.to_lowercase();
+        if keyboard.just_released(code) {
+            release(&mut state, &key);
+        }
+        if keyboard.just_pressed(code) && !typing && !modified {
+            press(&mut state, key);
+        }
+    }
+}
+
+/// Record mode settings, and the take in progress.
+pub fn draw_record_panel(ui: &mut egui::Ui, state: &mut EditorState) {
+    ui.heading("Record Mode");
+
+    ui.checkbox(&mut state.record_mode, "⏺ Record key presses during playback");
+    ui.checkbox(&mut state.record_quantize, "Snap to the beat divisor");
+    egui::Grid::new("record_grid").num_columns(2).show(ui, |ui| {
+        ui.label("Input latency (ms)");
+        ui.add(egui::DragValue::new(&mut state.input_latency_ms).speed(1).clamp_range(-500..=500))
+            .on_hover_text("Subtracted from each press, here and in playtests; raise it if notes land late");
+        ui.end_row();
+
+        ui.label("Hold after (ms)");
+        ui.add(egui::DragValue::new(&mut state.record_hold_ms).speed(5).clamp_range(50..=2000))
+            .on_hover_text("Keys held at least this long become hold notes");
+        ui.end_row();
+    });
+
+    let count = state.record_take.as_ref().map(|t| t.notes.len() + t.held.len());
+    if let Some(count) = count {
+        ui.horizontal(|ui| {
+            ui.colored_label(egui::Color32::from_rgb(230, 80, 80), format!("Recording: {} notes", count));
+            if ui.button("🗑 Discard take").clicked() {
+                discard_take(state);
+            }
+        });
+    }
+}
@@ -0,0 +1,237 @@
+use crate::{
+    editor_state::EditorState,
+    hitsound_audio::{HitsoundBank, HitsoundChannel},
+    hitsound_util::{all_sample_names, sample_name, sample_set_problems, SAMPLE_EXTENSIONS},
+    history::{Edit, FileChange},
+};
+use anyhow::{anyhow, Context, Result};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_kira_audio::{AudioChannel, AudioControl};
+use std::path::Path;
+
+/// Package entries that provide the sample `name`, whatever their extension or folder.
+fn entries_for(state: &EditorState, name: &str) -> Vec<(String, usize)> {
+    let Some(pkg) = state.rtm_package.as_ref() else {
+        return Vec::new();
+    };
+    pkg.other_files
+        .iter()
+        .filter(|(entry, _)| sample_name(entry).as_deref() == Some(name))
+        .map(|(entry, bytes)| (entry.clone(), bytes.len()))
+        .collect()
+}
+
+/// Changes that store `bytes` as the sample `name` at the package root, replacing any
+/// existing file for that sample.
+fn set_sample_changes(state: &EditorState, name: &str, ext: &str, bytes: Vec<u8>) -> Vec<FileChange> {
+    let Some(pkg) = state.rtm_package.as_ref() else {
+        return Vec::new();
+    };
+    let entry = format!("{}.{}", name, ext);
+    let mut changes: Vec<FileChange> = entries_for(state, name)
+        .into_iter()
+        .filter(|(old, _)| *old != entry)
+        .map(|(old, _)| FileChange {
+            before: pkg.other_files.get(&old).cloned(),
+            name: old,
+            after: None,
+        })
+        .collect();
+    changes.push(FileChange {
+        before: pkg.other_files.get(&entry).cloned(),
+        name: entry,
+        after: Some(bytes),
+    });
+    changes
+}
+
+fn read_sample(path: &Path) -> Result<(String, Vec<u8>)> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .filter(|e| SAMPLE_EXTENSIONS.contains(&e.as_str()))
+        .ok_or_else(|| anyhow!("{} is not a wav/ogg/mp3/flac file", path.display()))?;
+    let bytes = std::fs::read(path).with_context(|| format!("read {}", path.display()))?;
+    Ok((ext, bytes))
+}
+
+fn import_into(state: &mut EditorState, name: &str) {
+    let Some(path) = rfd::FileDialog::new()
+        .add_filter("Audio", &SAMPLE_EXTENSIONS)
+        .pick_file()
+    else {
+        return;
+    };
+    match read_sample(&path) {
+        Ok((ext, bytes)) => {
+            let changes = set_sample_changes(state, name, &ext, bytes);
+            state.apply_edit(Edit::SetPackageFiles { changes });
+            state.status = format!("Imported {} as {}", path.display(), name);
+        }
+        Err(err) => state.status = format!("Import sample failed: {:#}", err),
+    }
+}
+
+/// Imports several files at once, taking the sample each one provides from its file name.
+fn import_named_files(state: &mut EditorState) {
+    let Some(paths) = rfd::FileDialog::new()
+        .add_filter("Audio", &SAMPLE_EXTENSIONS)
+        .pick_files()
+    else {
+        return;
+    };
+
+    let mut changes = Vec::new();
+    let mut skipped = Vec::new();
+    for path in paths {
+        let file = path.file_name().and_then(|s| s.to_str()).unwrap_or_default();
+        let Some(name) = sample_name(file) else {
+            skipped.push(file.to_string());
+            continue;
+        };
+        match read_sample(&path) {
+            Ok((ext, bytes)) => changes.extend(set_sample_changes(state, &name, &ext, bytes)),
+            Err(_) => skipped.push(file.to_string()),
+        }
+    }
+
+    let count = changes.iter().filter(|c| c.after.is_some()).count();
+    if !changes.is_empty() {
+        state.apply_edit(Edit::SetPackageFiles { changes });
+    }
+    state.status = if skipped.is_empty() {
+        format!("Imported {} samples", count)
+    } else {
+        format!(
+            "Imported {} samples; skipped {} (expected names like soft-hitclap.wav)",
+            count,
+            skipped.join(", ")
+        )
+    };
+}
+
+fn remove_sample(state: &mut EditorState, name: &str) {
+    let Some(pkg) = state.rtm_package.as_ref() else {
+        return;
+    };
+    let changes: Vec<FileChange> = entries_for(state, name)
+        .into_iter()
+        .map(|(entry, _)| FileChange {
+            before: pkg.other_files.get(&entry).cloned(),
+            name: entry,
+            after: None,
+        })
+        .collect();
+    if changes.is_empty() {
+        return;
+    }
+    state.apply_edit(Edit::SetPackageFiles { changes });
+    state.status = format!("Removed custom {}", name);
+}
+
+/// Lists the package's custom hitsound samples, one row per sample the game looks up.
+pub fn sample_window_system(
+    mut contexts: EguiContexts,
+    mut state: ResMut<EditorState>,
+    bank: Res<HitsoundBank>,
+    channel: Res<AudioChannel<HitsoundChannel>>,
+) {
+    if !state.show_samples {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+    let mut open = true;
+    let mut import: Option<String> = None;
+    let mut remove: Option<String> = None;
+    let mut import_many = false;
+
+    egui::Window::new("🔊 Hitsound Samples")
+        .open(&mut open)
+        .default_width(460.0)
+        .show(ctx, |ui| {
+            let Some(pkg) = state.rtm_package.as_ref() else {
+                ui.label("Load a mapset first.");
+                return;
+            };
+
+            ui.label(if state.meta.hasCustomHitsounds {
+                "Custom hitsounds are on. Samples missing here fall back to the defaults."
+            } else {
+                "No custom samples; the default hitsounds are used."
+            });
+            ui.weak("Files are named <set>-<sound>, e.g. soft-hitclap.wav or drum-holdloop.ogg.");
+            if ui.button("📂 Import named files…").clicked() {
+                import_many = true;
+            }
+
+            // The working beatmap is newer than its copy in the package.
+            for (idx, d) in pkg.difficulties.iter().enumerate() {
+                let beatmap = if idx == state.selected_difficulty {
+                    &state.beatmap
+                } else {
+                    &d.beatmap
+                };
+                for problem in sample_set_problems(pkg, state.meta.hasCustomHitsounds, beatmap) {
+                    ui.colored_label(
+                        egui::Color32::from_rgb(220, 190, 80),
+                        format!("[{}] {}", d.meta.name, problem),
+                    );
+                }
+            }
+
+            ui.separator();
+
+            egui::ScrollArea::vertical().max_height(380.0).show(ui, |ui| {
+                egui::Grid::new("samples_grid")
+                    .striped(true)
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        for name in all_sample_names() {
+                            ui.label(&name);
+
+                            let entries = entries_for(&state, &name);
+                            match entries.first() {
+                                Some((entry, len)) => {
+                                    ui.label(format!("{} ({:.1} KB)", entry, *len as f32 / 1024.0))
+                                }
+                                None => ui.weak("default"),
+                            };
+
+                            ui.horizontal(|ui| {
+                                if ui.small_button("▶").on_hover_text("Preview").clicked() {
+                                    if let Some(handle) = bank.get(&name) {
+                                        channel.play(handle);
+                                    }
+                                }
+                                let label = if entries.is_empty() { "Import…" } else { "Replace…" };
+                                if ui.small_button(label).clicked() {
+                                    import = Some(name.clone());
+                                }
+                                if ui
+                                    .add_enabled(!entries.is_empty(), egui::Button::new("🗑").small())
+                                    .on_hover_text("Remove the custom sample")
+                                    .clicked()
+                                {
+                                    remove = Some(name.clone());
+                                }
+                            });
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+
+    state.show_samples = open;
+
+    if import_many {
+        import_named_files(&mut state);
+    }
+    if let Some(name) = import {
+        import_into(&mut state, &name);
+    }
+    if let Some(name) = remove {
+        remove_sample(&mut state, &name);
+    }
+}
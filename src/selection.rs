@@ -0,0 +1,331 @@
+use crate::{
+    data::{key_position, Beatmap, BeatmapNote, KEYBOARD_LAYOUT},
+    editor_state::EditorState,
+    history::Edit,
+    timing_util::{meta_timing_points_sorted, snap_step_ms, snap_time_to_beat_divisor_ms, timing_point_at},
+};
+use std::collections::HashSet;
+
+/// Identifies a note within `Beatmap::notes` independently of its index, which shifts
+/// whenever notes are inserted or re-sorted.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NoteId {
+    pub key: String,
+    pub start: u32,
+    pub end: u32,
+}
+
+impl NoteId {
+    pub fn of(note: &BeatmapNote) -> Self {
+        NoteId {
+            key: note.key.to_lowercase(),
+            start: note.get_start_time(),
+            end: note.get_end_time(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    ids: HashSet<NoteId>,
+}
+
+impl Selection {
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.ids.clear();
+    }
+
+    pub fn contains(&self, note: &BeatmapNote) -> bool {
+        self.ids.contains(&NoteId::of(note))
+    }
+
+    pub fn insert(&mut self, note: &BeatmapNote) {
+        self.ids.insert(NoteId::of(note));
+    }
+
+    pub fn toggle(&mut self, note: &BeatmapNote) {
+        let id = NoteId::of(note);
+        if !self.ids.remove(&id) {
+            self.ids.insert(id);
+        }
+    }
+
+    /// Replaces the selection with exactly `notes`.
+    pub fn set(&mut self, notes: &[BeatmapNote]) {
+        self.ids = notes.iter().map(NoteId::of).collect();
+    }
+
+    pub fn select_all(&mut self, beatmap: &Beatmap) {
+        self.set(&beatmap.notes);
+    }
+
+    /// Selects notes that overlap `[t0, t1]` and sit on a keyboard row in `rows`.
+    pub fn select_box(
+        &mut self,
+        beatmap: &Beatmap,
+        t0: u32,
+        t1: u32,
+        rows: std::ops::RangeInclusive<usize>,
+        additive: bool,
+    ) {
+        if !additive {
+            self.ids.clear();
+        }
+        let (t0, t1) = (t0.min(t1), t0.max(t1));
+        for n in &beatmap.notes {
+            // Notes on keys off the layout have no row to box.
+            let Some((row, _)) = key_position(&n.key) else {
+                continue;
+            };
+            if rows.contains(&row) && n.get_start_time() <= t1 && n.get_end_time() >= t0 {
+                self.insert(n);
+            }
+        }
+    }
+
+    /// Selected notes that still exist in `beatmap`, in beatmap order.
+    pub fn notes(&self, beatmap: &Beatmap) -> Vec<BeatmapNote> {
+        beatmap
+            .notes
+            .iter()
+            .filter(|n| self.contains(n))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Moves notes by `delta_ms`, keeping hold lengths. Returns `None` if a note would move
+/// before 0ms.
+pub fn shift_notes(notes: &[BeatmapNote], delta_ms: i64) -> Option<Vec<BeatmapNote>> {
+    notes
+        .iter()
+        .map(|n| {
+            let mut moved = n.clone();
+            let shift = |t: u32| -> Option<u32> { u32::try_from(t as i64 + delta_ms).ok() };
+            moved.time = match n.time {
+                Some(t) => Some(shift(t)?),
+                None => None,
+            };
+            moved.startTime = match n.startTime {
+                Some(t) => Some(shift(t)?),
+                None => None,
+            };
+            moved.endTime = match n.endTime {
+                Some(t) => Some(shift(t)?),
+                None => None,
+            };
+            Some(moved)
+        })
+        .collect()
+}
+
+pub fn select_all(state: &mut EditorState) {
+    state.selection.select_all(&state.beatmap);
+    state.status = format!("Selected {} notes", state.selection.len());
+}
+
+pub fn delete_selected(state: &mut EditorState) {
+    let notes = state.selection.notes(&state.beatmap);
+    if notes.is_empty() {
+        return;
+    }
+    let count = notes.len();
+    state.apply_edit(Edit::RemoveNotes {
+        difficulty: state.selected_difficulty,
+        notes,
+    });
+    state.selection.clear();
+    state.status = format!("Deleted {} notes", count);
+}
+
+pub fn copy_selected(state: &mut EditorState) {
+    let notes = state.selection.notes(&state.beatmap);
+    let Some(first) = notes.iter().map(|n| n.get_start_time()).min() else {
+        return;
+    };
+    // Store clipboard notes relative to the earliest one so they paste at the playhead.
+    state.clipboard = shift_notes(&notes, -(first as i64)).unwrap_or_default();
+    state.status = format!("Copied {} notes", state.clipboard.len());
+}
+
+pub fn cut_selected(state: &mut EditorState) {
+    copy_selected(state);
+    delete_selected(state);
+    state.status = format!("Cut {} notes", state.clipboard.len());
+}
+
+pub fn paste_at_playhead(state: &mut EditorState) {
+    if state.clipboard.is_empty() {
+        return;
+    }
+    let timing_points = meta_timing_points_sorted(&state.meta);
+    let at = snap_time_to_beat_divisor_ms(&timing_points, state.current_time, state.beat_divisor);
+    let Some(notes) = shift_notes(&state.clipboard, at as i64) else {
+        return;
+    };
+    state.selection.set(&notes);
+    let count = notes.len();
+    state.apply_edit(Edit::AddNotes {
+        difficulty: state.selected_difficulty,
+        notes,
+    });
+    state.status = format!("Pasted {} notes @ {}ms", count, at);
+}
+
+/// Moves the selection one snap step earlier (`dir < 0`) or later (`dir > 0`).
+pub fn nudge_selected(state: &mut EditorState, dir: i64) {
+    let before = state.selection.notes(&state.beatmap);
+    let Some(first) = before.iter().map(|n| n.get_start_time()).min() else {
+        return;
+    };
+
+    // Step from the earliest note's grid so the whole selection stays on the grid.
+    let timing_points = meta_timing_points_sorted(&state.meta);
+    let tp = timing_point_at(&timing_points, first);
+    let target = first as f32 + dir as f32 * snap_step_ms(&tp, state.beat_divisor);
+    let snapped =
+        snap_time_to_beat_divisor_ms(&timing_points, target.max(0.0).round() as u32, state.beat_divisor);
+    let delta = snapped as i64 - first as i64;
+    if delta == 0 {
+        return;
+    }
+
+    let Some(after) = shift_notes(&before, delta) else {
+        return;
+    };
+    state.selection.set(&after);
+    state.apply_edit(Edit::ReplaceNotes {
+        difficulty: state.selected_difficulty,
+        before,
+        after,
+    });
+    state.status = format!("Moved {} notes by {}ms", state.selection.len(), delta);
+}
+
+/// Moves notes `delta_rows` keyboard rows up or down, keeping each note's column.
+/// Returns `None` if a note would leave the layout.
+pub fn shift_rows(notes: &[BeatmapNote], delta_rows: i64) -> Option<Vec<BeatmapNote>> {
+    notes
+        .iter()
+        .map(|n| {
+            let (row, col) = key_position(&n.key)?;
+            let row = usize::try_from(row as i64 + delta_rows).ok()?;
+            let key = KEYBOARD_LAYOUT.get(row)?.get(col)?;
+            let mut moved = n.clone();
+            moved.key = key.to_lowercase();
+            Some(moved)
+        })
+        .collect()
+}
+
+/// A timeline drag of one or more notes. The beatmap is updated live while dragging and
+/// the whole move is recorded as a single history entry on release.
+#[derive(Debug, Clone)]
+pub struct NoteDrag {
+    /// The dragged notes as they were when the drag started.
+    pub before: Vec<BeatmapNote>,
+    /// The dragged notes as they currently are in the beatmap.
+    pub current: Vec<BeatmapNote>,
+    /// Dragging a hold's tail changes only its `endTime`.
+    pub tail_only: bool,
+    /// Start (or end, for a tail drag) of the grabbed note when the drag started.
+    pub grab_ms: u32,
+    pub grab_row: usize,
+    /// Pointer time when the drag started.
+    pub press_ms: f32,
+}
+
+impl NoteDrag {
+    /// `None` if `grabbed` is on a key off the layout, which has no row to drag from.
+    pub fn new(notes: Vec<BeatmapNote>, grabbed: &BeatmapNote, tail_only: bool, press_ms: f32) -> Option<Self> {
+        let (grab_row, _) = key_position(&grabbed.key)?;
+        Some(NoteDrag {
+            before: notes.clone(),
+            current: notes,
+            tail_only,
+            grab_ms: if tail_only {
+                grabbed.get_end_time()
+            } else {
+                grabbed.get_start_time()
+            },
+            grab_row,
+            press_ms,
+        })
+    }
+}
+
+/// Moves the dragged notes to follow the pointer at `pointer_ms` on keyboard row `pointer_row`.
+pub fn update_note_drag(state: &mut EditorState, pointer_ms: f32, pointer_row: usize) {
+    let Some(drag) = state.timeline_note_drag.as_ref() else {
+        return;
+    };
+
+    let timing_points = meta_timing_points_sorted(&state.meta);
+    let target = (drag.grab_ms as f32 + pointer_ms - drag.press_ms).max(0.0).round() as u32;
+    let snapped = snap_time_to_beat_divisor_ms(&timing_points, target, state.beat_divisor);
+
+    let after = if drag.tail_only {
+        drag.before
+            .iter()
+            .map(|n| {
+                let start = n.startTime?;
+                (snapped > start).then(|| {
+                    let mut moved = n.clone();
+                    moved.endTime = Some(snapped);
+                    moved
+                })
+            })
+            .collect::<Option<Vec<_>>>()
+    } else {
+        let delta_ms = snapped as i64 - drag.grab_ms as i64;
+        let delta_rows = pointer_row as i64 - drag.grab_row as i64;
+        shift_notes(&drag.before, delta_ms).and_then(|moved| shift_rows(&moved, delta_rows))
+    };
+
+    // Out of range (before 0ms, off the layout, or a tail before its head): stay put.
+    let Some(after) = after else {
+        return;
+    };
+    if after == drag.current {
+        return;
+    }
+
+    state.beatmap.remove_notes(&drag.current);
+    state.beatmap.insert_notes(after.iter().cloned());
+    state.selection.set(&after);
+    if let Some(drag) = state.timeline_note_drag.as_mut() {
+        drag.current = after;
+    }
+}
+
+/// Ends a note drag, recording the move in the undo history.
+pub fn finish_note_drag(state: &mut EditorState) {
+    let Some(drag) = state.timeline_note_drag.take() else {
+        return;
+    };
+    if drag.current == drag.before {
+        return;
+    }
+
+    let count = drag.current.len();
+    // The beatmap already holds the moved notes, so record without re-applying.
+    state.history.push(Edit::ReplaceNotes {
+        difficulty: state.selected_difficulty,
+        before: drag.before,
+        after: drag.current,
+    });
+    state.mark_dirty();
+    state.status = if drag.tail_only {
+        "Resized hold".to_string()
+    } else {
+        format!("Moved {} notes", count)
+    };
+}
@@ -0,0 +1,224 @@
+use crate::data::{key_position, BeatmapNote};
+
+/// Length of one strain section; the curve has one peak per section.
+pub const SECTION_MS: u32 = 400;
+
+/// Strain left after one second without notes.
+const STRAIN_DECAY_PER_S: f64 = 0.3;
+/// Each section peak counts this much less than the next hardest one.
+const PEAK_WEIGHT_DECAY: f64 = 0.9;
+const STAR_SCALE: f64 = 0.3;
+/// Notes closer together than this count as one chord.
+const CHORD_MS: u32 = 5;
+
+/// A difficulty's overall rating and how hard each section of it is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StarRating {
+    pub stars: f64,
+    /// Peak strain of each `SECTION_MS` section, from the start of the map.
+    pub section_strains: Vec<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Hand {
+    Left,
+    Right,
+}
+
+/// Touch-typing finger (0 = left pinky .. 7 = right pinky) and hand for a key. Both index
+/// fingers cover two columns.
+fn finger(key: &str) -> Option<(u8, Hand)> {
+    let (_, col) = key_position(key)?;
+    let finger = match col {
+        0..=3 => col as u8,
+        4 => 3,
+        5 => 4,
+        c => c as u8 - 2,
+    };
+    let hand = if finger < 4 { Hand::Left } else { Hand::Right };
+    Some((finger, hand))
+}
+
+struct Chord<'a> {
+    time: u32,
+    notes: Vec<&'a BeatmapNote>,
+}
+
+fn chords(notes: &[BeatmapNote]) -> Vec<Chord<'_>> {
+    let mut sorted: Vec<&BeatmapNote> = notes.iter().collect();
+    sorted.sort_by_key(|n| n.get_start_time());
+    let mut out: Vec<Chord> = Vec::new();
+    for n in sorted {
+        let t = n.get_start_time();
+        match out.last_mut() {
+            Some(c) if t - c.time <= CHORD_MS => c.notes.push(n),
+            _ => out.push(Chord { time: t, notes: vec![n] }),
+        }
+    }
+    out
+}
+
+/// How much harder this chord is than a lone note, given the chord before it.
+fn chord_difficulty(chord: &Chord, prev: Option<&Chord>, holds: &[&BeatmapNote]) -> f64 {
+    let fingers: Vec<(String, u8, Hand)> = chord
+        .notes
+        .iter()
+        .filter_map(|n| finger(&n.key).map(|(f, h)| (n.key.to_lowercase(), f, h)))
+        .collect();
+
+    // Each extra key in a chord adds less than the one before.
+    let mut value = 1.0 + (chord.notes.len() as f64 - 1.0).max(0.0).sqrt() * 0.6;
+
+    if let Some(prev) = prev {
+        let prev_fingers: Vec<(String, u8, Hand)> = prev
+            .notes
+            .iter()
+            .filter_map(|n| finger(&n.key).map(|(f, h)| (n.key.to_lowercase(), f, h)))
+            .collect();
+        // One finger hitting two different keys in a row has to travel; the same key twice
+        // (a jack) only has to lift.
+        let travel = fingers
+            .iter()
+            .any(|(k, f, _)| prev_fingers.iter().any(|(pk, pf, _)| pf == f && pk != k));
+        let jack = fingers.iter().any(|(k, _, _)| prev_fingers.iter().any(|(pk, _, _)| pk == k));
+        if travel {
+            value *= 1.45;
+        } else if jack {
+            value *= 1.25;
+        }
+        // Alternating hands is easier than staying on one.
+        let same_hand = fingers
+            .iter()
+            .all(|(_, _, h)| prev_fingers.iter().any(|(_, _, ph)| ph == h));
+        if same_hand && !fingers.is_empty() {
+            value *= 1.15;
+        }
+    }
+
+    // Holds still down while this chord is hit tie up fingers.
+    let held = holds
+        .iter()
+        .filter(|n| n.get_start_time() + CHORD_MS < chord.time && n.get_end_time() > chord.time)
+        .count();
+    value * (1.0 + 0.2 * held as f64)
+}
+
+/// Rates a difficulty from its notes. Strain builds up with each chord, scaled by how
+/// hard the chord is and how soon it follows the last one, and decays between them.
+/// The hardest sections count most towards the stars.
+pub fn star_rating(notes: &[BeatmapNote]) -> StarRating {
+    let chords = chords(notes);
+    let Some(last) = chords.last() else {
+        return StarRating {
+            stars: 0.0,
+            section_strains: Vec::new(),
+        };
+    };
+    let mut section_strains = vec![0.0; (last.time / SECTION_MS) as usize + 1];
+    let holds: Vec<&BeatmapNote> = notes.iter().filter(|n| n.note_type == "hold").collect();
+
+    let mut strain = 0.0;
+    let mut prev: Option<&Chord> = None;
+    for chord in &chords {
+        let delta_ms = prev.map_or(1000, |p| chord.time - p.time).max(25) as f64;
+        strain *= STRAIN_DECAY_PER_S.powf(delta_ms / 1000.0);
+        strain += chord_difficulty(chord, prev, &holds) * 1000.0 / delta_ms;
+
+        let section = &mut section_strains[(chord.time / SECTION_MS) as usize];
+        *section = f64::max(*section, strain);
+        prev = Some(chord);
+    }
+
+    let mut peaks: Vec<f64> = section_strains.iter().copied().filter(|&s| s > 0.0).collect();
+    peaks.sort_by(|a, b| b.total_cmp(a));
+    let mut weight = 1.0;
+    let mut total = 0.0;
+    for p in peaks {
+        total += p * weight;
+        weight *= PEAK_WEIGHT_DECAY;
+    }
+
+    StarRating {
+        stars: total.sqrt() * STAR_SCALE,
+        section_strains,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `count` chords `interval_ms` apart, cycling through `chords` (keys per chord).
+    fn stream(chords: &[&str], interval_ms: u32, count: u32) -> Vec<BeatmapNote> {
+        (0..count)
+            .flat_map(|i| {
+                let keys = chords[i as usize % chords.len()];
+                keys.chars()
+                    .map(move |k| BeatmapNote::tap(k.to_string(), 1000 + i * interval_ms))
+            })
+            .collect()
+    }
+
+    fn stars(notes: &[BeatmapNote]) -> f64 {
+        star_rating(notes).stars
+    }
+
+    #[test]
+    fn an_empty_map_has_no_stars() {
+        let rating = star_rating(&[]);
+        assert_eq!(rating.stars, 0.0);
+        assert!(rating.section_strains.is_empty());
+    }
+
+    #[test]
+    fn denser_maps_rate_higher() {
+        let slow = stars(&stream(&["f", "j"], 250, 64));
+        let fast = stars(&stream(&["f", "j"], 125, 128));
+        let faster = stars(&stream(&["f", "j"], 80, 200));
+        assert!(0.0 < slow && slow < fast && fast < faster, "{} {} {}", slow, fast, faster);
+    }
+
+    #[test]
+    fn bigger_chords_rate_higher() {
+        let singles = stars(&stream(&["f", "j"], 200, 64));
+        let pairs = stars(&stream(&["fd", "jk"], 200, 64));
+        let quads = stars(&stream(&["fdsa", "jkl;"], 200, 64));
+        assert!(singles < pairs && pairs < quads, "{} {} {}", singles, pairs, quads);
+    }
+
+    #[test]
+    fn finger_patterns_rank_by_effort() {
+        // Alternating hands, then one hand with different fingers, then a jack on one key,
+        // then one finger moving between two keys.
+        let alternating = stars(&stream(&["f", "j"], 150, 64));
+        let same_hand = stars(&stream(&["a", "f"], 150, 64));
+        let jack = stars(&stream(&["f"], 150, 64));
+        let travel = stars(&stream(&["f", "g"], 150, 64));
+        assert!(
+            alternating < same_hand && same_hand < jack && jack < travel,
+            "{} {} {} {}",
+            alternating,
+            same_hand,
+            jack,
+            travel
+        );
+    }
+
+    #[test]
+    fn a_burst_raises_the_rating_and_peaks_the_curve() {
+        let easy = stream(&["f", "j"], 400, 40);
+        let burst_start = 21_000;
+        let mut with_burst = easy.clone();
+        with_burst.extend(stream(&["f", "j"], 60, 40).into_iter().map(|n| {
+            let time = n.get_start_time() - 1000 + burst_start;
+            BeatmapNote::tap(n.key, time)
+        }));
+
+        let rating = star_rating(&with_burst);
+        assert!(rating.stars > stars(&easy));
+        let hardest = (0..rating.section_strains.len())
+            .max_by(|&a, &b| rating.section_strains[a].total_cmp(&rating.section_strains[b]))
+            .unwrap();
+        assert!(hardest >= (burst_start / SECTION_MS) as usize);
+    }
+}
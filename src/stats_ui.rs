@@ -0,0 +1,185 @@
+use crate::{
+    data::BeatmapNote,
+    editor_state::EditorState,
+    map_stats::{format_duration, map_stats, MapStats},
+    rating_ui,
+    star_rating::star_rating,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+/// Stats and stars of one difficulty, with the notes they were computed from.
+pub struct CachedStats {
+    notes: Vec<BeatmapNote>,
+    stats: MapStats,
+    stars: f64,
+}
+
+/// The stats of difficulty `idx`, recomputed only when its notes change. `stars` rates the
+/// notes, so the working difficulty can share `EditorState::star_rating_cache`.
+fn stats_for<'a>(
+    cache: &'a mut Vec<Option<CachedStats>>,
+    idx: usize,
+    notes: &[BeatmapNote],
+    stars: impl FnOnce() -> f64,
+) -> &'a CachedStats {
+    if cache.len() <= idx {
+        cache.resize_with(idx + 1, || None);
+    }
+    let entry = &mut cache[idx];
+    if !matches!(entry, Some(e) if e.notes == notes) {
+        *entry = Some(CachedStats {
+            notes: notes.to_vec(),
+            stats: map_stats(notes),
+            stars: stars(),
+        });
+    }
+    entry.as_ref().expect("filled above")
+}
+
+fn draw_nps_graph(ui: &mut egui::Ui, state: &mut EditorState, stats: &MapStats) {
+    let size = egui::vec2(ui.available_width(), 70.0);
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, egui::Color32::from_rgb(20, 20, 20));
+
+    let count = stats.nps_curve.len().max(1);
+    let max = stats.nps_curve.iter().copied().max().unwrap_or(0).max(1) as f32;
+    let bar_w = rect.width() / count as f32;
+    for (i, &n) in stats.nps_curve.iter().enumerate() {
+        let h = n as f32 / max * (rect.height() - 4.0);
+        let x = rect.left() + i as f32 * bar_w;
+        let bar = egui::Rect::from_min_max(
+            egui::pos2(x, rect.bottom() - h),
+            egui::pos2(x + bar_w.max(1.0), rect.bottom()),
+        );
+        painter.rect_filled(bar, 0.0, egui::Color32::from_rgb(90, 140, 210));
+    }
+    let playhead_x = rect.left() + state.current_time as f32 / 1000.0 * bar_w;
+    painter.line_segment(
+        [egui::pos2(playhead_x, rect.top()), egui::pos2(playhead_x, rect.bottom())],
+        egui::Stroke::new(1.0, egui::Color32::from_rgb(255, 80, 80)),
+    );
+
+    let response = response.on_hover_text(format!("Notes per second (peak {:.0}); click to seek", max));
+    if response.clicked() {
+        if let Some(pos) = response.interact_pointer_pos() {
+            let ms = ((pos.x - rect.left()) / bar_w * 1000.0).max(0.0) as u32;
+            state.current_time = ms;
+            state.audio_seek_request = Some(ms);
+        }
+    }
+}
+
+/// Statistics for the working difficulty, and a table comparing every difficulty.
+pub fn stats_window_system(
+    mut contexts: EguiContexts,
+    mut state: ResMut<EditorState>,
+    mut cache: Local<Vec<Option<CachedStats>>>,
+) {
+    if !state.show_stats {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+    let mut open = true;
+
+    egui::Window::new("📊 Statistics")
+        .open(&mut open)
+        .default_width(420.0)
+        .show(ctx, |ui| {
+            let selected = state.selected_difficulty;
+            let selected_stars = rating_ui::current_rating(&mut state).stars;
+            let notes = state.beatmap.notes.clone();
+            let current = stats_for(&mut cache, selected, &notes, || selected_stars);
+            let (stats, stars) = (current.stats.clone(), current.stars);
+
+            ui.heading(state.beatmap.name.clone());
+            egui::Grid::new("stats_grid").num_columns(2).striped(true).show(ui, |ui| {
+                ui.label("Notes");
+                ui.label(format!(
+                    "{} ({} taps, {} holds)",
+                    stats.taps + stats.holds,
+                    stats.taps,
+                    stats.holds
+                ));
+                ui.end_row();
+                ui.label("Drain time");
+                ui.label(format_duration(stats.drain_ms));
+                ui.end_row();
+                ui.label("Notes per second");
+                ui.label(format!("{:.2} average, {} peak", stats.avg_nps, stats.peak_nps));
+                ui.end_row();
+                ui.label("Longest stream");
+                if stats.longest_stream > 0 {
+                    let at = stats.longest_stream_start_ms;
+                    let text = format!("{} notes @ {}", stats.longest_stream, format_duration(at));
+                    if ui.link(text).on_hover_text("Seek to the stream").clicked() {
+                        state.current_time = at;
+                        state.audio_seek_request = Some(at);
+                    }
+                } else {
+                    ui.label("none");
+                }
+                ui.end_row();
+                ui.label("Star rating");
+                ui.label(format!("{:.2}★", stars));
+                ui.end_row();
+            });
+
+            if !stats.nps_curve.is_empty() {
+                draw_nps_graph(ui, &mut state, &stats);
+            }
+
+            ui.label("Chords");
+            let total: usize = stats.chord_sizes.values().sum();
+            for (size, count) in &stats.chord_sizes {
+                let label = if *size == 1 {
+                    "Single notes".to_string()
+                } else {
+                    format!("{} keys", size)
+                };
+                ui.add(
+                    egui::ProgressBar::new(*count as f32 / total.max(1) as f32)
+                        .text(format!("{}: {}", label, count)),
+                );
+            }
+
+            ui.checkbox(&mut state.show_key_heatmap, "Show key usage on the keyboard");
+
+            egui::CollapsingHeader::new("Compare difficulties").show(ui, |ui| {
+                let Some(pkg) = state.rtm_package.as_ref() else {
+                    ui.label("Load a mapset to compare its difficulties.");
+                    return;
+                };
+                egui::Grid::new("stats_compare_grid")
+                    .num_columns(6)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for heading in ["Difficulty", "Stars", "Notes", "Drain", "Avg NPS", "Peak NPS"] {
+                            ui.strong(heading);
+                        }
+                        ui.end_row();
+
+                        for (idx, d) in pkg.difficulties.iter().enumerate() {
+                            // The package copy of the working difficulty may be stale.
+                            let row = if idx == selected {
+                                stats_for(&mut cache, idx, &state.beatmap.notes, || selected_stars)
+                            } else {
+                                let notes = &d.beatmap.notes;
+                                stats_for(&mut cache, idx, notes, || star_rating(notes).stars)
+                            };
+                            let name = egui::RichText::new(&d.meta.name);
+                            ui.label(if idx == selected { name.strong() } else { name });
+                            ui.label(format!("{:.2}★", row.stars));
+                            ui.label(format!("{}", row.stats.taps + row.stats.holds));
+                            ui.label(format_duration(row.stats.drain_ms));
+                            ui.label(format!("{:.2}", row.stats.avg_nps));
+                            ui.label(format!("{}", row.stats.peak_nps));
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+
+    state.show_stats = open;
+}
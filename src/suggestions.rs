@@ -0,0 +1,163 @@
+use crate::{
+    data::{BeatmapNote, KEYBOARD_LAYOUT},
+    editor_state::EditorState,
+    history::Edit,
+    tempo::detect_onsets,
+    timing_util::{meta_timing_points_sorted, snap_time_to_beat_divisor_ms},
+};
+use bevy::prelude::*;
+use bevy::tasks::{block_on, poll_once, AsyncComputeTaskPool, Task};
+use bevy_egui::egui;
+use bevy_kira_audio::AudioSource;
+use std::sync::Arc;
+
+/// The onset detection running for the loaded song, if any.
+#[derive(Resource, Default)]
+pub struct OnsetLoader {
+    source: Option<AssetId<AudioSource>>,
+    task: Option<Task<Vec<u32>>>,
+}
+
+/// Detects the song's onsets off the UI thread the first time suggestions are shown for it,
+/// and stores them unsnapped in `EditorState::onsets`.
+pub fn onset_system(
+    mut state: ResMut<EditorState>,
+    mut loader: ResMut<OnsetLoader>,
+    sources: Res<Assets<AudioSource>>,
+) {
+    let id = state.audio_handle.as_ref().map(|h| h.id());
+    if id != loader.source {
+        loader.source = None;
+        loader.task = None;
+        state.onsets = None;
+        let source = id.and_then(|id| sources.get(id));
+        if let Some(source) = source.filter(|_| state.show_suggestions) {
+            let sound = source.sound.clone();
+            let task = AsyncComputeTaskPool::get().spawn(async move {
+                let samples: Vec<f32> = sound.frames.iter().map(|f| (f.left + f.right) * 0.5).collect();
+                detect_onsets(&samples, sound.sample_rate)
+                    .into_iter()
+                    .map(|(t, _)| t.round() as u32)
+                    .collect()
+            });
+            loader.task = Some(task);
+            loader.source = id;
+        }
+    }
+
+    if let Some(task) = loader.task.as_mut() {
+        if let Some(onsets) = block_on(poll_once(task)) {
+            state.onsets = Some(Arc::new(onsets));
+            loader.task = None;
+        }
+    }
+}
+
+/// Suggested note times in `[from_ms, to_ms]`: the song's onsets snapped to the current
+/// beat divisor, without duplicates. Snapping happens here so suggestions follow timing
+/// and divisor changes.
+pub fn suggested_times(state: &EditorState, from_ms: u32, to_ms: u32) -> Vec<u32> {
+    let Some(onsets) = state.onsets.as_ref() else {
+        return Vec::new();
+    };
+    let points = meta_timing_points_sorted(&state.meta);
+    // An onset up to one beat outside the range can still snap into it.
+    let margin = 60_000.0 / points.first().map_or(state.meta.bpm, |p| p.bpm).max(1.0);
+    let lo = onsets.partition_point(|&t| (t as f64) < from_ms as f64 - margin);
+    let hi = onsets.partition_point(|&t| (t as f64) <= to_ms as f64 + margin);
+
+    let mut times: Vec<u32> = onsets[lo..hi]
+        .iter()
+        .map(|&t| snap_time_to_beat_divisor_ms(&points, t, state.beat_divisor))
+        .filter(|&t| t >= from_ms && t <= to_ms)
+        .collect();
+    times.sort_unstable();
+    times.dedup();
+    times
+}
+
+/// Adds a tap note on `suggestion_key` at every suggestion in `suggestion_range_ms`, skipping
+/// times where that key already has a note.
+fn accept_suggestions(state: &mut EditorState) {
+    let (from, to) = state.suggestion_range_ms;
+    let key = state.suggestion_key.to_lowercase();
+    let notes: Vec<BeatmapNote> = suggested_times(state, from.min(to), from.max(to))
+        .into_iter()
+        .filter(|&t| {
+            !state.beatmap.notes.iter().any(|n| {
+                n.key.eq_ignore_ascii_case(&key) && n.get_start_time() <= t && t <= n.get_end_time()
+            })
+        })
+        .map(|t| BeatmapNote::tap(key.clone(), t))
+        .collect();
+
+    if notes.is_empty() {
+        state.status = "No new notes to add from suggestions".to_string();
+        return;
+    }
+    let count = notes.len();
+    state.apply_edit(Edit::AddNotes {
+        difficulty: state.selected_difficulty,
+        notes,
+    });
+    state.status = format!("Added {} notes from suggestions on {}", count, key.to_uppercase());
+}
+
+/// Shows or hides the suggestion markers and turns the suggestions in a range into notes.
+pub fn draw_suggestions(ui: &mut egui::Ui, state: &mut EditorState) {
+    ui.heading("Note Suggestions");
+
+    ui.checkbox(&mut state.show_suggestions, "Show suggestions on the timeline");
+    if !state.show_suggestions {
+        return;
+    }
+    if state.audio_handle.is_none() {
+        ui.label("No song loaded.");
+        return;
+    }
+    if state.onsets.is_none() {
+        ui.horizontal(|ui| {
+            ui.spinner();
+            ui.label("Finding onsets…");
+        });
+        return;
+    }
+
+    let now_ms = state.current_time;
+    egui::Grid::new("suggestion_range_grid").num_columns(3).show(ui, |ui| {
+        ui.label("From (ms)");
+        ui.add(egui::DragValue::new(&mut state.suggestion_range_ms.0).speed(1));
+        if ui.small_button("⏵ Playhead").clicked() {
+            state.suggestion_range_ms.0 = now_ms;
+        }
+        ui.end_row();
+
+        ui.label("To (ms)");
+        ui.add(egui::DragValue::new(&mut state.suggestion_range_ms.1).speed(1));
+        if ui.small_button("⏵ Playhead").clicked() {
+            state.suggestion_range_ms.1 = now_ms;
+        }
+        ui.end_row();
+
+        ui.label("Key");
+        egui::ComboBox::from_id_source("suggestion_key")
+            .selected_text(state.suggestion_key.as_str())
+            .width(48.0)
+            .show_ui(ui, |ui| {
+                for key in KEYBOARD_LAYOUT.iter().flat_map(|row| row.iter()) {
+                    ui.selectable_value(&mut state.suggestion_key, key.to_string(), *key);
+                }
+            });
+        ui.end_row();
+    });
+
+    let (from, to) = state.suggestion_range_ms;
+    let count = suggested_times(state, from.min(to), from.max(to)).len();
+    if ui
+        .add_enabled(count > 0, egui::Button::new(format!("Add {} as taps", count)))
+        .on_hover_text("Suggestions are only markers until added here")
+        .clicked()
+    {
+        accept_suggestions(state);
+    }
+}
@@ -0,0 +1,283 @@
+use crate::{data::TimingPoint, editor_state::EditorState, timing_util::sync_timing_point};
+use bevy::prelude::*;
+use bevy::tasks::{block_on, poll_once, AsyncComputeTaskPool, Task};
+use bevy_kira_audio::AudioSource;
+use std::f64::consts::TAU;
+
+const MIN_BPM: f64 = 60.0;
+const MAX_BPM: f64 = 240.0;
+const COARSE_STEP_BPM: f64 = 0.1;
+const FINE_STEP_BPM: f64 = 0.005;
+const MAX_CANDIDATES: usize = 4;
+
+/// A detected tempo and the time of its first beat.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimingCandidate {
+    pub bpm: f64,
+    pub offset: i64,
+    /// 0..1: how tightly onsets line up with the beat grid times how many beats have one.
+    pub confidence: f32,
+}
+
+impl TimingCandidate {
+    pub fn to_timing_point(&self) -> TimingPoint {
+        let mut tp = TimingPoint {
+            id: 0.0,
+            time: 0.0,
+            bpm: self.bpm,
+            offset: self.offset,
+            timeSignature: [4, 4],
+        };
+        sync_timing_point(&mut tp);
+        tp
+    }
+}
+
+/// Onset times (ms) and strengths, from rises in short-window loudness.
+pub fn detect_onsets(samples: &[f32], sample_rate: u32) -> Vec<(f64, f32)> {
+    let hop = (sample_rate as usize / 400).max(1);
+    let window = hop * 8;
+    let hop_ms = hop as f64 * 1000.0 / sample_rate as f64;
+
+    // rms[i] covers the window that ends with hop i.
+    let rms: Vec<f32> = (0..samples.len() / hop)
+        .map(|i| {
+            let end = (i + 1) * hop;
+            let w = &samples[end.saturating_sub(window)..end];
+            (w.iter().map(|s| s * s).sum::<f32>() / w.len() as f32).sqrt()
+        })
+        .collect();
+    let novelty: Vec<f32> = (0..rms.len())
+        .map(|i| if i == 0 { 0.0 } else { (rms[i] - rms[i - 1]).max(0.0) })
+        .collect();
+    if novelty.is_empty() {
+        return Vec::new();
+    }
+
+    let mean = novelty.iter().sum::<f32>() / novelty.len() as f32;
+    let var = novelty.iter().map(|n| (n - mean) * (n - mean)).sum::<f32>() / novelty.len() as f32;
+    let threshold = mean + var.sqrt();
+    // Peaks closer than this belong to the same onset.
+    let radius = (50.0 / hop_ms).ceil() as usize;
+
+    (0..novelty.len())
+        .filter(|&i| {
+            let n = novelty[i];
+            let lo = i.saturating_sub(radius);
+            let hi = (i + radius + 1).min(novelty.len());
+            // Ties go to the earliest frame so a flat peak counts once.
+            n > threshold
+                && novelty[lo..i].iter().all(|&m| m < n)
+                && novelty[i + 1..hi].iter().all(|&m| m <= n)
+        })
+        // The onset happened somewhere inside hop i; take its middle.
+        .map(|i| ((i as f64 + 0.5) * hop_ms, novelty[i]))
+        .collect()
+}
+
+/// How tightly the onsets line up on a grid with this period, 0..1, and the grid's phase
+/// in ms.
+fn phase_coherence(onsets: &[(f64, f32)], period_ms: f64) -> (f64, f64) {
+    let (mut re, mut im, mut total) = (0.0, 0.0, 0.0);
+    for &(t, w) in onsets {
+        let angle = TAU * t / period_ms;
+        re += w as f64 * angle.cos();
+        im += w as f64 * angle.sin();
+        total += w as f64;
+    }
+    if total <= 0.0 {
+        return (0.0, 0.0);
+    }
+    let phase = (im.atan2(re) / TAU * period_ms).rem_euclid(period_ms);
+    ((re * re + im * im).sqrt() / total, phase)
+}
+
+/// Places the grid's first beat on the first onset that sits on it, and scores the grid.
+fn candidate(onsets: &[(f64, f32)], bpm: f64) -> Option<TimingCandidate> {
+    let period = 60_000.0 / bpm;
+    let (coherence, phase) = phase_coherence(onsets, period);
+    let tolerance = (period * 0.15).min(40.0);
+    let off_grid = |t: f64| {
+        let d = (t - phase).rem_euclid(period);
+        d.min(period - d)
+    };
+
+    let first = onsets.iter().find(|&&(t, _)| off_grid(t) <= tolerance)?.0;
+    let offset = phase + ((first - phase) / period).round() * period;
+    let last = onsets.last()?.0;
+
+    // The share of beats from the first to the last onset that have an onset near them.
+    // This stops a multiple of the real tempo from scoring as well as the tempo itself.
+    let beats = ((last - offset) / period).floor() as usize + 1;
+    let mut hit = 0;
+    let mut j = 0;
+    for k in 0..beats {
+        let beat = offset + k as f64 * period;
+        while j < onsets.len() && onsets[j].0 < beat - tolerance {
+            j += 1;
+        }
+        if j < onsets.len() && onsets[j].0 <= beat + tolerance {
+            hit += 1;
+        }
+    }
+    let coverage = hit as f64 / beats as f64;
+
+    Some(TimingCandidate {
+        bpm,
+        offset: offset.round() as i64,
+        confidence: (coherence * coverage) as f32,
+    })
+}
+
+/// Estimates the song's tempo and first beat. Returns the best candidates first, usually
+/// including half or double the top tempo when those also fit.
+pub fn detect_timing(samples: &[f32], sample_rate: u32) -> Vec<TimingCandidate> {
+    let onsets = detect_onsets(samples, sample_rate);
+    if onsets.len() < 4 {
+        return Vec::new();
+    }
+
+    let steps = ((MAX_BPM - MIN_BPM) / COARSE_STEP_BPM).round() as usize;
+    let coarse: Vec<(f64, f64)> = (0..=steps)
+        .map(|i| {
+            let bpm = MIN_BPM + i as f64 * COARSE_STEP_BPM;
+            (bpm, phase_coherence(&onsets, 60_000.0 / bpm).0)
+        })
+        .collect();
+
+    let coherence_at = |i: usize| coarse.get(i).map_or(f64::MIN, |c| c.1);
+    let mut peaks: Vec<(f64, f64)> = (0..coarse.len())
+        .filter(|&i| {
+            let before = i.checked_sub(1).map_or(f64::MIN, coherence_at);
+            coarse[i].1 >= before && coarse[i].1 > coherence_at(i + 1)
+        })
+        .map(|i| coarse[i])
+        .collect();
+    peaks.sort_by(|a, b| b.1.total_cmp(&a.1));
+    peaks.truncate(MAX_CANDIDATES * 4);
+
+    let mut out: Vec<TimingCandidate> = Vec::new();
+    for (bpm, _) in peaks {
+        // Narrow the peak down, then settle on the two-decimal BPM mappers would type.
+        let fine_steps = (COARSE_STEP_BPM / FINE_STEP_BPM).round() as i64;
+        let best = (-fine_steps..=fine_steps)
+            .map(|s| bpm + s as f64 * FINE_STEP_BPM)
+            .filter(|b| (MIN_BPM..=MAX_BPM).contains(b))
+            .max_by(|a, b| {
+                let ca = phase_coherence(&onsets, 60_000.0 / a).0;
+                let cb = phase_coherence(&onsets, 60_000.0 / b).0;
+                ca.total_cmp(&cb)
+            })
+            .unwrap_or(bpm);
+        let rounded = (best * 100.0).round() / 100.0;
+        if let Some(c) = candidate(&onsets, rounded) {
+            if !out.iter().any(|o| (o.bpm - c.bpm).abs() < 0.5) {
+                out.push(c);
+            }
+        }
+    }
+    out.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    // Near-misses around a strong tempo score a little and would only be noise.
+    let best = out.first().map_or(0.0, |c| c.confidence);
+    out.retain(|c| c.confidence >= best * 0.25);
+    out.truncate(MAX_CANDIDATES);
+    out
+}
+
+/// Tempo detection running in the background for `EditorState::timing_detect_requested`.
+#[derive(Resource, Default)]
+pub struct TimingDetector {
+    task: Option<Task<Vec<TimingCandidate>>>,
+}
+
+/// Runs `detect_timing` on the loaded song off the UI thread and stores the result in
+/// `EditorState::timing_candidates`.
+pub fn timing_detect_system(
+    mut state: ResMut<EditorState>,
+    mut detector: ResMut<TimingDetector>,
+    sources: Res<Assets<AudioSource>>,
+) {
+    if state.timing_detect_requested {
+        state.timing_detect_requested = false;
+        let source = state.audio_handle.as_ref().and_then(|h| sources.get(h));
+        match source {
+            Some(source) => {
+                let sound = source.sound.clone();
+                let task = AsyncComputeTaskPool::get().spawn(async move {
+                    let samples: Vec<f32> = sound.frames.iter().map(|f| (f.left + f.right) * 0.5).collect();
+                    detect_timing(&samples, sound.sample_rate)
+                });
+                detector.task = Some(task);
+                state.timing_candidates.clear();
+                state.status = "Detecting BPM…".to_string();
+            }
+            None => state.status = "Detect BPM: the song hasn't loaded yet".to_string(),
+        }
+    }
+
+    if let Some(task) = detector.task.as_mut() {
+        if let Some(candidates) = block_on(poll_once(task)) {
+            state.status = match candidates.first() {
+                Some(best) => format!("Detected {:.2} BPM, offset {}ms", best.bpm, best.offset),
+                None => "Detect BPM: no clear beat found".to_string(),
+            };
+            state.timing_candidates = candidates;
+            detector.task = None;
+        }
+    }
+    state.timing_detect_running = detector.task.is_some();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 44_100;
+
+    /// `seconds` of short decaying 1 kHz clicks on every beat, the first at `offset_ms`.
+    fn click_track(bpm: f64, offset_ms: f64, seconds: f64) -> Vec<f32> {
+        let mut samples = vec![0.0f32; (seconds * RATE as f64) as usize];
+        let beat_ms = 60_000.0 / bpm;
+        let click_len = RATE as usize / 50;
+        let mut t = offset_ms;
+        while t < seconds * 1000.0 {
+            let start = (t / 1000.0 * RATE as f64) as usize;
+            for i in 0..click_len.min(samples.len().saturating_sub(start)) {
+                let env = 1.0 - i as f32 / click_len as f32;
+                samples[start + i] = 0.8 * env * (TAU as f32 * 1000.0 * i as f32 / RATE as f32).sin();
+            }
+            t += beat_ms;
+        }
+        samples
+    }
+
+    /// How far `offset` is from the nearest beat of the grid through `expected`.
+    fn beat_error(offset: i64, expected: f64, bpm: f64) -> f64 {
+        let beat = 60_000.0 / bpm;
+        let phase = (offset as f64 - expected).rem_euclid(beat);
+        phase.min(beat - phase)
+    }
+
+    #[test]
+    fn finds_the_tempo_and_offset_of_click_tracks() {
+        for (bpm, offset) in [(120.0, 250.0), (174.0, 80.0), (95.5, 610.0)] {
+            let best = detect_timing(&click_track(bpm, offset, 20.0), RATE)
+                .into_iter()
+                .next()
+                .unwrap_or_else(|| panic!("no tempo found for {} BPM", bpm));
+            assert!((best.bpm - bpm).abs() < 0.05, "{} BPM detected as {}", bpm, best.bpm);
+            assert!(
+                beat_error(best.offset, offset, bpm) <= 10.0,
+                "{} BPM at {}ms: offset {}",
+                bpm,
+                offset,
+                best.offset
+            );
+        }
+    }
+
+    #[test]
+    fn silence_has_no_tempo() {
+        assert!(detect_timing(&vec![0.0; RATE as usize * 5], RATE).is_empty());
+    }
+}
@@ -1,9 +1,34 @@
 use crate::{
+    data::{key_position, BeatmapNote, KEYBOARD_LAYOUT},
     editor_state::EditorState,
-    timing_util::{beat_len_ms, meta_timing_points_sorted, snap_time_to_beat_divisor_ms, timing_point_at},
+    selection::{finish_note_drag, update_note_drag, NoteDrag},
+    suggestions::suggested_times,
+    timing_util::{meta_timing_points_sorted, snap_step_ms, snap_time_to_beat_divisor_ms, timing_point_at},
 };
 use bevy_egui::egui;
 
+/// Grid line colour for ticks on the 1/`divisor` grid, following osu!'s scheme.
+pub fn tick_color(divisor: u32) -> egui::Color32 {
+    match divisor {
+        1 => egui::Color32::from_rgb(200, 200, 200),
+        2 => egui::Color32::from_rgb(190, 70, 70),
+        3 => egui::Color32::from_rgb(150, 90, 200),
+        4 => egui::Color32::from_rgb(80, 120, 210),
+        6 => egui::Color32::from_rgb(200, 130, 220),
+        8 => egui::Color32::from_rgb(210, 190, 70),
+        12 => egui::Color32::from_rgb(120, 120, 120),
+        _ => egui::Color32::from_rgb(90, 90, 90),
+    }
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
 pub fn draw_timeline(ui: &mut egui::Ui, ctx: &egui::Context, state: &mut EditorState) {
     ui.group(|ui| {
         ui.heading("⏱️ Timeline");
@@ -82,12 +107,12 @@ pub fn draw_timeline(ui: &mut egui::Ui, ctx: &egui::Context, state: &mut EditorS
                 }
             } else {
                 if wheel.abs() > 0.0 {
-                    // Scrub by 1/8 notes (half-beats) so scrolling matches the visible grid.
+                    // Scrub by one snap step so scrolling matches the visible grid.
                     // If we're not already on the grid, snap first.
                     let timing_points = meta_timing_points_sorted(&state.meta);
-                    let snapped_start = snap_time_to_beat_divisor_ms(&timing_points, state.current_time, 2);
+                    let snapped_start = snap_time_to_beat_divisor_ms(&timing_points, state.current_time, state.beat_divisor);
                     let tp = timing_point_at(&timing_points, snapped_start);
-                    let step_ms = (beat_len_ms(&tp) * 0.5).round().max(1.0) as i64;
+                    let step_ms = snap_step_ms(&tp, state.beat_divisor).round().max(1.0) as i64;
 
                     // Normalize wheel delta into discrete notches
                     let notches = ((wheel.abs() / 40.0).round() as i64).clamp(1, 16);
@@ -96,47 +121,172 @@ pub fn draw_timeline(ui: &mut egui::Ui, ctx: &egui::Context, state: &mut EditorS
 
                     let new_time = snapped_start as i64 + delta_ms;
                     let unsnapped = new_time.max(0) as u32;
-                    state.current_time = snap_time_to_beat_divisor_ms(&timing_points, unsnapped, 2);
+                    state.current_time = snap_time_to_beat_divisor_ms(&timing_points, unsnapped, state.beat_divisor);
                     state.audio_seek_request = Some(state.current_time);
                     ctx.request_repaint();
                 }
             }
         }
 
-        // Click seeks to the clicked time
+        // Helpers for note placement on screen
+        let row_count = KEYBOARD_LAYOUT.len();
+        let key_row = |k: &str| -> usize { key_position(k).map(|(row, _)| row).unwrap_or(row_count - 1) };
+        let row_y = |row: usize| -> f32 { (rect.top() + 34.0) + row as f32 * row_spacing };
+        let time_at_x = |x: f32| -> f32 { view_start_ms + (x - rect.left()) * ms_per_pixel };
+
+        // Topmost note under the pointer, as an index into beatmap.notes
+        let note_at = |pos: egui::Pos2, notes: &[BeatmapNote]| -> Option<usize> {
+            let hit_ms = 6.0 * ms_per_pixel;
+            notes.iter().rposition(|n| {
+                let y = row_y(key_row(&n.key));
+                if (pos.y - y).abs() > 8.0 {
+                    return false;
+                }
+                let t = time_at_x(pos.x);
+                let start = n.get_start_time() as f32;
+                let end = n.get_end_time() as f32;
+                t >= start - hit_ms && t <= end + hit_ms
+            })
+        };
+
+        let shift_held = ctx.input(|i| i.modifiers.shift);
+
+        // Click selects the note under the pointer, otherwise seeks to the clicked time
         if response.clicked() {
             if let Some(pos) = response.interact_pointer_pos() {
-                let t = view_start_ms + (pos.x - rect.left()) * ms_per_pixel;
-                state.current_time = t.max(0.0).round() as u32;
-                state.audio_seek_request = Some(state.current_time);
+                if let Some(idx) = note_at(pos, &state.beatmap.notes) {
+                    let note = state.beatmap.notes[idx].clone();
+                    if shift_held {
+                        state.selection.toggle(&note);
+                    } else {
+                        state.selection.set(std::slice::from_ref(&note));
+                    }
+                } else {
+                    if !shift_held {
+                        state.selection.clear();
+                    }
+                    let t = time_at_x(pos.x);
+                    state.current_time = t.max(0.0).round() as u32;
+                    state.audio_seek_request = Some(state.current_time);
+                }
             }
         }
 
-        // Drag scrubs time (horizontal)
-        if response.drag_started() {
-            state.timeline_drag_last_dx = Some(0.0);
+        // Left drag on a note moves it (a hold's tail resizes it); elsewhere it scrubs time
+        if response.drag_started_by(egui::PointerButton::Primary) {
+            let press = ctx.input(|i| i.pointer.press_origin());
+            let grabbed = press.and_then(|pos| Some((pos, note_at(pos, &state.beatmap.notes)?)));
+            if let Some((pos, idx)) = grabbed {
+                let note = state.beatmap.notes[idx].clone();
+                let end_x = rect.left() + (note.get_end_time() as f32 - view_start_ms) * pixels_per_ms;
+                let tail_only = note.note_type == "hold" && (pos.x - end_x).abs() <= 6.0;
+
+                // Grabbing part of the selection drags all of it; a tail only ever resizes one hold.
+                let notes = if !tail_only && state.selection.contains(&note) {
+                    state.selection.notes(&state.beatmap)
+                } else {
+                    vec![note.clone()]
+                };
+                state.selection.set(&notes);
+                state.timeline_note_drag = NoteDrag::new(notes, &note, tail_only, time_at_x(pos.x));
+                if state.timeline_note_drag.is_none() {
+                    state.status = format!("Can't drag: '{}' is not on the keyboard layout", note.key);
+                }
+            } else {
+                state.timeline_drag_last_dx = Some(0.0);
+            }
         }
-        if response.dragged() {
-            let total_dx = response.drag_delta().x;
-            let last_dx = state.timeline_drag_last_dx.unwrap_or(0.0);
-            let frame_dx = total_dx - last_dx;
-            state.timeline_drag_last_dx = Some(total_dx);
-
-            let delta_ms = (-frame_dx * ms_per_pixel).round() as i64;
-            let new_time = state.current_time as i64 + delta_ms;
-            state.current_time = new_time.max(0) as u32;
-            state.audio_seek_request = Some(state.current_time);
+        if response.dragged_by(egui::PointerButton::Primary) {
+            if state.timeline_note_drag.is_some() {
+                if let Some(pos) = response.interact_pointer_pos() {
+                    let rows_top = rect.top() + 34.0;
+                    let row = ((pos.y - rows_top) / row_spacing)
+                        .round()
+                        .clamp(0.0, (row_count - 1) as f32) as usize;
+                    update_note_drag(state, time_at_x(pos.x), row);
+                }
+            } else {
+                let total_dx = response.drag_delta().x;
+                let last_dx = state.timeline_drag_last_dx.unwrap_or(0.0);
+                let frame_dx = total_dx - last_dx;
+                state.timeline_drag_last_dx = Some(total_dx);
+
+                let delta_ms = (-frame_dx * ms_per_pixel).round() as i64;
+                let new_time = state.current_time as i64 + delta_ms;
+                state.current_time = new_time.max(0) as u32;
+                state.audio_seek_request = Some(state.current_time);
+            }
         }
-        if response.drag_stopped() {
+        if response.drag_stopped_by(egui::PointerButton::Primary) {
+            finish_note_drag(state);
             state.timeline_drag_last_dx = None;
         }
 
+        // Right drag draws a selection box over time and key rows (shift adds to the selection).
+        // The anchor is kept in time so it stays put if the view moves during playback.
+        if response.drag_started_by(egui::PointerButton::Secondary) {
+            if let Some(pos) = response.interact_pointer_pos() {
+                state.timeline_box_anchor = Some((time_at_x(pos.x), pos.y));
+            }
+        }
+        let box_rect = state.timeline_box_anchor.and_then(|(t0, y0)| {
+            let pos = response.interact_pointer_pos()?;
+            let x0 = rect.left() + (t0 - view_start_ms) * pixels_per_ms;
+            Some(egui::Rect::from_two_pos(egui::pos2(x0, y0), pos))
+        });
+        if response.drag_stopped_by(egui::PointerButton::Secondary) {
+            if let Some(r) = box_rect {
+                let rows: Vec<usize> = (0..row_count)
+                    .filter(|&row| {
+                        let y = row_y(row);
+                        y >= r.top() - 8.0 && y <= r.bottom() + 8.0
+                    })
+                    .collect();
+                if let (Some(&first), Some(&last)) = (rows.first(), rows.last()) {
+                    let t0 = time_at_x(r.left()).max(0.0).round() as u32;
+                    let t1 = time_at_x(r.right()).max(0.0).round() as u32;
+                    state.selection.select_box(&state.beatmap, t0, t1, first..=last, shift_held);
+                } else if !shift_held {
+                    state.selection.clear();
+                }
+                state.status = format!("Selected {} notes", state.selection.len());
+            }
+            state.timeline_box_anchor = None;
+        }
+
         // Clip all drawing to the timeline area so it never overlaps other panels
         let painter = ui.painter_at(rect);
 
         // Draw timeline background
         painter.rect_filled(rect, 0.0, egui::Color32::from_rgb(30, 30, 30));
 
+        // Draw the song's waveform behind everything else, one column per pixel
+        if let Some(waveform) = state.waveform.as_ref() {
+            let content_top = rect.top() + 18.0;
+            let mid = (content_top + rect.bottom()) * 0.5;
+            let half_h = (rect.bottom() - content_top) * 0.5;
+            let peak_color = egui::Color32::from_rgb(55, 70, 90);
+            let rms_color = egui::Color32::from_rgb(75, 100, 130);
+            let mut x = rect.left().floor();
+            while x < rect.right() {
+                let t0 = view_start_ms + (x - rect.left()) * ms_per_pixel;
+                if let Some(b) = waveform.range(t0, t0 + ms_per_pixel) {
+                    let top = mid - b.max.clamp(-1.0, 1.0) * half_h;
+                    let bottom = (mid - b.min.clamp(-1.0, 1.0) * half_h).max(top + 1.0);
+                    painter.line_segment(
+                        [egui::pos2(x, top), egui::pos2(x, bottom)],
+                        egui::Stroke::new(1.0, peak_color),
+                    );
+                    let rms = b.rms.min(1.0) * half_h;
+                    painter.line_segment(
+                        [egui::pos2(x, mid - rms), egui::pos2(x, mid + rms)],
+                        egui::Stroke::new(1.0, rms_color),
+                    );
+                }
+                x += 1.0;
+            }
+        }
+
         // Draw timeline from meta timing points
         let timing_points = meta_timing_points_sorted(&state.meta);
         let active_tp = timing_point_at(&timing_points, state.current_time);
@@ -170,38 +320,39 @@ pub fn draw_timeline(ui: &mut egui::Ui, ctx: &egui::Context, state: &mut EditorS
                 continue;
             }
 
-            let bpm: f32 = (tp.bpm as f32).max(1.0);
-            // We interpret BPM as quarter-notes per minute and derive beat length from the
-            // time signature denominator so timing points can change the timeline.
-            let quarter_len: f32 = 60_000.0 / bpm;
-            let numer = tp.timeSignature[0].max(1) as f32;
-            let denom = tp.timeSignature[1].max(1) as f32;
-            let beat_len: f32 = (quarter_len * (4.0 / denom)).max(1.0);
-            let measure_len: f32 = beat_len * numer;
+            let numer = tp.timeSignature[0].max(1);
+            let divisor = state.beat_divisor.max(1);
+            let sub = snap_step_ms(tp, divisor);
 
-            let sub: f32 = (beat_len * 0.5).max(1.0);
+            // Skip subdivisions that would be packed tighter than a few pixels apart.
+            let tick_stride = if sub * pixels_per_ms < 3.0 { divisor as i64 } else { 1 };
 
-            let mut t = {
-                let k = ((draw_start - seg_start) / sub).ceil();
-                seg_start + k * sub
-            };
+            let first_tick = ((draw_start - seg_start) / sub).ceil() as i64;
+            let last_tick = ((draw_end + 0.5 - seg_start) / sub).floor() as i64;
 
-            while t <= draw_end + 0.5 {
+            let mut tick = first_tick;
+            while tick <= last_tick {
+                if tick % tick_stride != 0 {
+                    tick += 1;
+                    continue;
+                }
+                let t = seg_start + tick as f32 * sub;
                 let x = rect.left() + (t - view_start_ms) * pixels_per_ms;
                 if x >= rect.left() && x <= rect.right() {
-                    let rel = (t - seg_start).max(0.0);
-                    let rel_m = rel % measure_len;
-                    let rel_b = rel % beat_len;
-                    let is_measure = rel_m < 0.5 || (measure_len - rel_m) < 0.5;
-                    let is_beat = rel_b < 0.5 || (beat_len - rel_b) < 0.5;
+                    // Reduce the tick's position within the beat to find which grid it belongs
+                    // to, e.g. tick 2 of 1/4 sits on the 1/2 grid.
+                    let pos_in_beat = tick.rem_euclid(divisor as i64) as u32;
+                    let tick_divisor = divisor / gcd(pos_in_beat, divisor);
+                    let is_beat = pos_in_beat == 0;
+                    let is_measure = is_beat && (tick / divisor as i64).rem_euclid(numer) == 0;
 
                     let stroke = if is_measure {
                         egui::Stroke::new(1.5, egui::Color32::from_rgb(95, 95, 95))
-                    } else if is_beat {
-                        egui::Stroke::new(1.0, egui::Color32::from_rgb(75, 75, 75))
                     } else {
-                        // 1/8 notes
-                        egui::Stroke::new(0.75, egui::Color32::from_rgb(60, 60, 60))
+                        egui::Stroke::new(
+                            if is_beat { 1.0 } else { 0.75 },
+                            tick_color(tick_divisor),
+                        )
                     };
 
                     let header_h = 18.0;
@@ -212,7 +363,11 @@ pub fn draw_timeline(ui: &mut egui::Ui, ctx: &egui::Context, state: &mut EditorS
                         (rect.top(), rect.bottom())
                     } else {
                         let content_h = (content_bottom - content_top).max(1.0);
-                        let line_frac = if is_beat { 0.60 } else { 0.35 };
+                        let line_frac = match tick_divisor {
+                            1 => 0.60,
+                            2 => 0.45,
+                            _ => 0.35,
+                        };
                         let min_h = if is_beat { 10.0 } else { 6.0 };
                         let line_h = (content_h * line_frac).clamp(min_h, content_h);
                         let mid = (content_top + content_bottom) * 0.5;
@@ -225,20 +380,19 @@ pub fn draw_timeline(ui: &mut egui::Ui, ctx: &egui::Context, state: &mut EditorS
 
                     painter.line_segment([egui::pos2(x, y0), egui::pos2(x, y1)], stroke);
                 }
-                t += sub;
+                tick += 1;
             }
         }
 
-        // Helper for note vertical placement
-        let key_row = |k: &str| -> usize {
-            match k {
-                "q" | "w" | "e" | "r" | "t" | "y" | "u" | "i" | "o" | "p" => 0,
-                "a" | "s" | "d" | "f" | "g" | "h" | "j" | "k" | "l" | ";" => 1,
-                _ => 2,
+        // Draw onset suggestions as faint markers; they are never notes until accepted
+        if state.show_suggestions {
+            let from = view_start_ms.max(0.0) as u32;
+            let marker = egui::Stroke::new(2.0, egui::Color32::from_rgba_unmultiplied(240, 200, 90, 60));
+            for t in suggested_times(state, from, view_end_ms.max(0.0) as u32) {
+                let x = rect.left() + (t as f32 - view_start_ms) * pixels_per_ms;
+                painter.line_segment([egui::pos2(x, rect.top() + 18.0), egui::pos2(x, rect.bottom())], marker);
             }
-        };
-
-        let row_y = |row: usize| -> f32 { (rect.top() + 34.0) + row as f32 * row_spacing };
+        }
 
         // Draw notes
         for note in &state.beatmap.notes {
@@ -256,7 +410,12 @@ pub fn draw_timeline(ui: &mut egui::Ui, ctx: &egui::Context, state: &mut EditorS
             let row = key_row(note.key.as_str());
             let y = row_y(row);
 
-            let color = egui::Color32::from_rgb(180, 180, 180);
+            let selected = state.selection.contains(note);
+            let color = if selected {
+                egui::Color32::from_rgb(110, 170, 255)
+            } else {
+                egui::Color32::from_rgb(180, 180, 180)
+            };
 
             if note.note_type == "hold" {
                 let r = egui::Rect::from_min_max(
@@ -264,8 +423,14 @@ pub fn draw_timeline(ui: &mut egui::Ui, ctx: &egui::Context, state: &mut EditorS
                     egui::pos2(x_end.max(x_start + 2.0), y + 6.0),
                 );
                 painter.rect_filled(r, 3.0, color);
+                if selected {
+                    painter.rect_stroke(r.expand(1.5), 3.0, egui::Stroke::new(1.0, egui::Color32::WHITE));
+                }
             } else {
                 painter.circle_filled(egui::pos2(x_start, y), 5.0, color);
+                if selected {
+                    painter.circle_stroke(egui::pos2(x_start, y), 6.5, egui::Stroke::new(1.0, egui::Color32::WHITE));
+                }
             }
 
             // key label
@@ -280,7 +445,7 @@ pub fn draw_timeline(ui: &mut egui::Ui, ctx: &egui::Context, state: &mut EditorS
 
         // show any keys that are currently toggled on
         if !state.hold_starts.is_empty() {
-            let snapped_now = snap_time_to_beat_divisor_ms(&timing_points, state.current_time, 2) as f32;
+            let snapped_now = snap_time_to_beat_divisor_ms(&timing_points, state.current_time, state.beat_divisor) as f32;
             let hold_color = egui::Color32::from_rgba_premultiplied(200, 200, 200, 110);
 
             for (key, &start_ms) in state.hold_starts.iter() {
@@ -316,7 +481,7 @@ pub fn draw_timeline(ui: &mut egui::Ui, ctx: &egui::Context, state: &mut EditorS
 
         // show where a click would place a note
         if let Some(hover_key) = state.hovered_key.as_deref() {
-            let snapped_time = snap_time_to_beat_divisor_ms(&timing_points, state.current_time, 2) as f32;
+            let snapped_time = snap_time_to_beat_divisor_ms(&timing_points, state.current_time, state.beat_divisor) as f32;
 
             let ghost_color = egui::Color32::from_rgba_premultiplied(200, 200, 200, 90);
 
@@ -354,6 +519,15 @@ pub fn draw_timeline(ui: &mut egui::Ui, ctx: &egui::Context, state: &mut EditorS
             }
         }
 
+        if let Some(r) = box_rect {
+            painter.rect(
+                r,
+                0.0,
+                egui::Color32::from_rgba_unmultiplied(110, 170, 255, 40),
+                egui::Stroke::new(1.0, egui::Color32::from_rgb(110, 170, 255)),
+            );
+        }
+
         // Draw fixed playhead
         painter.line_segment(
             [egui::pos2(playhead_x, rect.top()), egui::pos2(playhead_x, rect.bottom())],
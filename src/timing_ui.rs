@@ -0,0 +1,222 @@
+use crate::{
+    editor_state::EditorState,
+    timing_util::{
+        insert_timing_point, meta_timing_points_sorted, snap_time_to_beat_divisor_ms,
+        split_timing_section, sync_meta_timing, sync_timing_point,
+    },
+};
+use bevy_egui::egui;
+
+/// Lists every timing point with inline editing. Returns true if any point changed.
+pub fn draw_timing_points(ui: &mut egui::Ui, state: &mut EditorState) -> bool {
+    let mut changed = false;
+    let mut resort = false;
+    let mut delete_idx: Option<usize> = None;
+    let mut seek_to: Option<u32> = None;
+
+    ui.heading("Timing Points");
+
+    if ui.button("➕ Add at playhead").clicked() {
+        let timing_points = meta_timing_points_sorted(&state.meta);
+        let snapped_time = snap_time_to_beat_divisor_ms(&timing_points, state.current_time, state.beat_divisor);
+        let count_before = state.meta.timingPoints.len();
+        let idx = insert_timing_point(&mut state.meta, snapped_time);
+        if state.meta.timingPoints.len() != count_before {
+            state.history.seal();
+            state.status = format!("Added timing point #{} @ {}ms", idx, snapped_time);
+            changed = true;
+        } else {
+            state.status = format!("Timing point already exists @ {}ms", snapped_time);
+        }
+    }
+
+    if ui
+        .button("✂ Split at playhead")
+        .on_hover_text("Start a new section on the beat nearest the playhead, keeping the beat grid")
+        .clicked()
+    {
+        let count_before = state.meta.timingPoints.len();
+        let (idx, offset) = split_timing_section(&mut state.meta, state.current_time);
+        if state.meta.timingPoints.len() != count_before {
+            state.history.seal();
+            state.status = format!("Split timing at #{} @ {}ms", idx, offset);
+            changed = true;
+        } else {
+            state.status = format!("Timing point already exists @ {}ms", offset);
+        }
+    }
+
+    changed |= draw_detected_timing(ui, state);
+
+    if state.meta.timingPoints.is_empty() {
+        ui.label("No timing points; using the song BPM and offset.");
+
+        ui.label("BPM");
+        changed |= ui
+            .add(egui::DragValue::new(&mut state.meta.bpm).speed(0.1))
+            .changed();
+
+        ui.label("Offset (ms)");
+        changed |= ui
+            .add(egui::DragValue::new(&mut state.meta.offset).speed(1))
+            .changed();
+
+        return changed;
+    }
+
+    let now_ms = state.current_time as i64;
+    let active_idx = state
+        .meta
+        .timingPoints
+        .iter()
+        .rposition(|tp| tp.offset <= now_ms)
+        .unwrap_or(0);
+    let can_delete = state.meta.timingPoints.len() > 1;
+
+    egui::ScrollArea::vertical()
+        .id_source("timing_points_scroll")
+        .max_height(260.0)
+        .show(ui, |ui| {
+            egui::Grid::new("timing_points_grid")
+                .striped(true)
+                .num_columns(5)
+                .show(ui, |ui| {
+                    ui.label("#");
+                    ui.label("Offset (ms)");
+                    ui.label("BPM");
+                    ui.label("Sig");
+                    ui.label("");
+                    ui.end_row();
+
+                    for (i, tp) in state.meta.timingPoints.iter_mut().enumerate() {
+                        let mut row_changed = false;
+                        let label = egui::RichText::new(format!("{}", i));
+                        if i == active_idx {
+                            ui.label(label.strong().color(egui::Color32::from_rgb(0, 255, 0)));
+                        } else {
+                            ui.label(label);
+                        }
+
+                        let offset_resp = ui.add(egui::DragValue::new(&mut tp.offset).speed(1));
+                        row_changed |= offset_resp.changed();
+                        // Only re-sort once the edit is done, otherwise rows would jump
+                        // under the cursor while dragging past a neighbour.
+                        if offset_resp.drag_stopped() || offset_resp.lost_focus() {
+                            resort = true;
+                        }
+
+                        row_changed |= ui
+                            .add(egui::DragValue::new(&mut tp.bpm).speed(0.1).clamp_range(1.0..=1000.0))
+                            .changed();
+
+                        ui.horizontal(|ui| {
+                            row_changed |= ui
+                                .add(egui::DragValue::new(&mut tp.timeSignature[0]).clamp_range(1..=32))
+                                .changed();
+                            ui.label("/");
+                            row_changed |= ui
+                                .add(egui::DragValue::new(&mut tp.timeSignature[1]).clamp_range(1..=32))
+                                .changed();
+                        });
+
+                        ui.horizontal(|ui| {
+                            if ui.small_button("⏵").on_hover_text("Seek to this point").clicked() {
+                                seek_to = Some(tp.offset.max(0) as u32);
+                            }
+                            if ui
+                                .add_enabled(can_delete, egui::Button::new("🗑").small())
+                                .on_hover_text("Delete this point")
+                                .clicked()
+                            {
+                                delete_idx = Some(i);
+                            }
+                        });
+                        ui.end_row();
+
+                        // Only the edited point is normalized; loaded values are left as they are.
+                        if row_changed {
+                            sync_timing_point(tp);
+                            changed = true;
+                        }
+                    }
+                });
+        });
+
+    if let Some(idx) = delete_idx {
+        let removed = state.meta.timingPoints.remove(idx);
+        state.history.seal();
+        state.status = format!("Deleted timing point @ {}ms", removed.offset);
+        changed = true;
+    }
+
+    // Focus leaving an untouched field also ends an "edit", so only reorder when needed.
+    let unsorted = state.meta.timingPoints.windows(2).any(|w| w[1].offset < w[0].offset);
+    if resort && unsorted {
+        state.meta.timingPoints.sort_by_key(|tp| tp.offset);
+        changed = true;
+    }
+    if changed {
+        // Keep meta.bpm/offset mirrored from the first point.
+        if let Some(first) = state.meta.timingPoints.first() {
+            state.meta.bpm = first.bpm;
+            state.meta.offset = first.offset;
+        }
+    }
+
+    if let Some(ms) = seek_to {
+        state.current_time = ms;
+        state.audio_seek_request = Some(ms);
+    }
+
+    changed
+}
+
+/// The Detect BPM button and its results. Using a candidate replaces every timing point.
+fn draw_detected_timing(ui: &mut egui::Ui, state: &mut EditorState) -> bool {
+    let mut changed = false;
+
+    ui.horizontal(|ui| {
+        let running = state.timing_detect_running;
+        if ui
+            .add_enabled(!running && state.audio_handle.is_some(), egui::Button::new("🔍 Detect BPM"))
+            .on_hover_text("Estimate the BPM and offset from the song")
+            .clicked()
+        {
+            state.timing_detect_requested = true;
+        }
+        if running {
+            ui.spinner();
+        }
+    });
+
+    let mut accept: Option<usize> = None;
+    for (i, c) in state.timing_candidates.iter().enumerate() {
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "{:.2} BPM @ {}ms ({:.0}%)",
+                c.bpm,
+                c.offset,
+                c.confidence * 100.0
+            ));
+            if ui
+                .small_button("Use")
+                .on_hover_text("Replace the timing points with this BPM and offset")
+                .clicked()
+            {
+                accept = Some(i);
+            }
+        });
+    }
+
+    if let Some(i) = accept {
+        let c = state.timing_candidates.remove(i);
+        state.meta.timingPoints = vec![c.to_timing_point()];
+        sync_meta_timing(&mut state.meta);
+        state.timing_candidates.clear();
+        state.history.seal();
+        state.status = format!("Timing set to {:.2} BPM @ {}ms", c.bpm, c.offset);
+        changed = true;
+    }
+
+    changed
+}
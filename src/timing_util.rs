@@ -43,21 +43,223 @@ pub fn beat_len_ms(tp: &TimingPoint) -> f32 {
     (quarter_len * (4.0 / denom)).max(1.0)
 }
 
+/// Snap divisors offered in the editor, as in osu!: 1/N splits each beat into N steps.
+pub const BEAT_DIVISORS: &[u32] = &[1, 2, 3, 4, 6, 8, 12, 16];
+
 // beat_divisor of 2 means half-beat (the 1/8 grid in 4/4).
 pub fn snap_time_to_beat_divisor_ms(points: &[TimingPoint], time_ms: u32, beat_divisor: u32) -> u32 {
     if points.is_empty() {
         return time_ms;
     }
 
-    let divisor = beat_divisor.max(1) as f32;
     let tp = timing_point_at(points, time_ms);
     let seg_start = tp.offset as f32;
 
-    let beat_len = beat_len_ms(&tp);
-    let step = (beat_len / divisor).max(1.0);
+    let step = snap_step_ms(&tp, beat_divisor);
 
     let t = time_ms as f32;
     let k = ((t - seg_start) / step).round();
     let snapped = seg_start + k * step;
     snapped.max(0.0).round() as u32
 }
+
+/// Length of one snap step in ms for the given timing point.
+pub fn snap_step_ms(tp: &TimingPoint, beat_divisor: u32) -> f32 {
+    (beat_len_ms(tp) / beat_divisor.max(1) as f32).max(1.0)
+}
+
+/// Derives the redundant fields of a timing point from its offset (ms).
+pub fn sync_timing_point(tp: &mut TimingPoint) {
+    tp.time = (tp.offset as f64) / 1000.0;
+    tp.id = tp.offset as f64;
+    tp.bpm = tp.bpm.max(1.0);
+    tp.timeSignature = [tp.timeSignature[0].max(1), tp.timeSignature[1].max(1)];
+}
+
+/// Sorts `meta.timingPoints`, syncs their derived fields and mirrors the first point into
+/// `meta.bpm`/`meta.offset`.
+pub fn sync_meta_timing(meta: &mut Meta) {
+    for tp in meta.timingPoints.iter_mut() {
+        sync_timing_point(tp);
+    }
+    meta.timingPoints.sort_by_key(|tp| tp.offset);
+    if let Some(first) = meta.timingPoints.first() {
+        meta.bpm = first.bpm;
+        meta.offset = first.offset;
+    }
+}
+
+/// Inserts a timing point at `time_ms` that inherits BPM and time signature from the point
+/// active there. Returns the index of the point at `time_ms` (existing or new). Only the new
+/// point is normalized; the others are left exactly as they are.
+pub fn insert_timing_point(meta: &mut Meta, time_ms: u32) -> usize {
+    if meta.timingPoints.is_empty() {
+        // Make the implicit fallback point explicit so it survives the insert.
+        meta.timingPoints = meta_timing_points_sorted(meta);
+    }
+
+    let offset = time_ms as i64;
+    if let Some(idx) = meta.timingPoints.iter().position(|tp| tp.offset == offset) {
+        return idx;
+    }
+
+    let prev = timing_point_at(&meta_timing_points_sorted(meta), time_ms);
+    let mut tp = TimingPoint {
+        id: 0.0,
+        time: 0.0,
+        bpm: prev.bpm,
+        offset,
+        timeSignature: prev.timeSignature,
+    };
+    sync_timing_point(&mut tp);
+
+    let idx = meta.timingPoints.partition_point(|p| p.offset < offset);
+    meta.timingPoints.insert(idx, tp);
+    if idx == 0 {
+        meta.bpm = meta.timingPoints[0].bpm;
+        meta.offset = meta.timingPoints[0].offset;
+    }
+    idx
+}
+
+/// Splits the section active at `time_ms` on its nearest beat, so the new point continues
+/// the same beat grid. Returns the index and offset of the point there (existing or new).
+pub fn split_timing_section(meta: &mut Meta, time_ms: u32) -> (usize, u32) {
+    let points = meta_timing_points_sorted(meta);
+    let beat = snap_time_to_beat_divisor_ms(&points, time_ms, 1);
+    (insert_timing_point(meta, beat), beat)
+}
+
+/// Beats in `(from_ms, to_ms]` as `(time_ms, is_downbeat)`, following every timing point.
+/// Each point restarts the bar count; beats before the first point extend it backwards.
+pub fn beats_in_range(points: &[TimingPoint], from_ms: u32, to_ms: u32) -> Vec<(u32, bool)> {
+    let mut out = Vec::new();
+    for (idx, tp) in points.iter().enumerate() {
+        let beat = beat_len_ms(tp) as f64;
+        let numer = tp.timeSignature[0].max(1);
+        let lo = if idx == 0 {
+            from_ms as i64
+        } else {
+            (from_ms as i64).max(tp.offset - 1)
+        };
+        let hi = match points.get(idx + 1) {
+            Some(next) => (to_ms as i64).min(next.offset - 1),
+            None => to_ms as i64,
+        };
+        if hi <= lo {
+            continue;
+        }
+        // First beat index strictly after `lo`.
+        let mut k = ((lo - tp.offset) as f64 / beat).floor() as i64 + 1;
+        loop {
+            let t = (tp.offset as f64 + k as f64 * beat).round() as i64;
+            if t > hi {
+                break;
+            }
+            if t > lo && t >= 0 {
+                out.push((t as u32, k.rem_euclid(numer) == 0));
+            }
+            k += 1;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(offset: i64, bpm: f64) -> TimingPoint {
+        let mut tp = TimingPoint {
+            id: 0.0,
+            time: 0.0,
+            bpm,
+            offset,
+            timeSignature: [4, 4],
+        };
+        sync_timing_point(&mut tp);
+        tp
+    }
+
+    fn meta(points: Vec<TimingPoint>) -> Meta {
+        Meta {
+            bpm: points.first().map_or(0.0, |p| p.bpm),
+            offset: points.first().map_or(0, |p| p.offset),
+            timingPoints: points,
+            ..Meta::default()
+        }
+    }
+
+    #[test]
+    fn insert_keeps_points_sorted() {
+        let mut m = meta(vec![point(0, 120.0), point(10_000, 150.0)]);
+        assert_eq!(insert_timing_point(&mut m, 5_000), 1);
+        assert_eq!(insert_timing_point(&mut m, 12_000), 3);
+        let offsets: Vec<i64> = m.timingPoints.iter().map(|p| p.offset).collect();
+        assert_eq!(offsets, [0, 5_000, 10_000, 12_000]);
+
+        // A point already there is reused.
+        assert_eq!(insert_timing_point(&mut m, 5_000), 1);
+        assert_eq!(m.timingPoints.len(), 4);
+    }
+
+    #[test]
+    fn inserted_points_inherit_the_previous_bpm() {
+        let mut m = meta(vec![point(0, 120.0), point(10_000, 150.0)]);
+        m.timingPoints[1].timeSignature = [3, 4];
+        let idx = insert_timing_point(&mut m, 12_000);
+        let tp = &m.timingPoints[idx];
+        assert_eq!((tp.bpm, tp.timeSignature), (150.0, [3, 4]));
+        assert_eq!((tp.id, tp.time), (12_000.0, 12.0));
+        // Not the first point, so the mirrored values stay.
+        assert_eq!((m.bpm, m.offset), (120.0, 0));
+    }
+
+    #[test]
+    fn untouched_points_keep_their_fields() {
+        let mut stale = point(1_000, 120.0);
+        stale.id = 7.0;
+        stale.time = 99.0;
+        let mut m = meta(vec![stale.clone(), point(10_000, 150.0)]);
+        let later = m.timingPoints[1].clone();
+
+        insert_timing_point(&mut m, 500);
+        insert_timing_point(&mut m, 5_000);
+        assert_eq!(m.timingPoints[1], stale);
+        assert_eq!(m.timingPoints[3], later);
+    }
+
+    #[test]
+    fn inserting_before_the_first_point_updates_the_mirror() {
+        let mut m = meta(vec![point(1_000, 120.0)]);
+        assert_eq!(insert_timing_point(&mut m, 200), 0);
+        assert_eq!((m.bpm, m.offset), (120.0, 200));
+    }
+
+    #[test]
+    fn insert_into_a_meta_without_points_keeps_the_implicit_one() {
+        let mut m = Meta {
+            bpm: 100.0,
+            offset: 50,
+            ..Meta::default()
+        };
+        assert_eq!(insert_timing_point(&mut m, 3_000), 1);
+        let offsets: Vec<i64> = m.timingPoints.iter().map(|p| p.offset).collect();
+        assert_eq!(offsets, [50, 3_000]);
+        assert_eq!(m.timingPoints[1].bpm, 100.0);
+    }
+
+    #[test]
+    fn split_lands_on_the_nearest_beat() {
+        // 120 BPM from 100ms: beats every 500ms.
+        let mut m = meta(vec![point(100, 120.0), point(20_000, 180.0)]);
+        let (idx, offset) = split_timing_section(&mut m, 2_380);
+        assert_eq!((idx, offset), (1, 2_600));
+        assert_eq!(m.timingPoints[1].bpm, 120.0);
+        assert_eq!(m.timingPoints.len(), 3);
+
+        // Splitting on an existing point doesn't add another.
+        assert_eq!(split_timing_section(&mut m, 20_010), (2, 20_000));
+        assert_eq!(m.timingPoints.len(), 3);
+    }
+}
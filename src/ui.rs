@@ -1,30 +1,52 @@
 use bevy::prelude::*;
 use bevy_egui::{egui, EguiContexts};
-use bevy_kira_audio::{Audio, AudioControl, AudioInstance, AudioTween};
+use bevy_kira_audio::{Audio, AudioControl, AudioInstance, AudioSource, AudioTween};
 
 use crate::{
     audio_util,
+    autosave,
+    background,
     data,
-    editor_state::{EditorState, AUDIO_POLL_INTERVAL_S},
+    editor_state::{EditorState, PendingAction, AUDIO_POLL_INTERVAL_S, PLAYBACK_RATES},
+    history::{BeatmapInfo, DifficultySnapshot, Edit},
+    hitsound_ui,
     keyboard_ui,
     loader,
+    meta_ui,
+    playtest,
+    rating_ui,
+    record,
+    selection,
+    suggestions,
     timeline_ui,
+    timing_ui,
+    timing_util,
+    validation::{self, Severity},
 };
 
 pub fn setup(mut commands: Commands) {
     commands.spawn(Camera2d::default());
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn ui_system(
     mut contexts: EguiContexts,
     asset_server: Res<AssetServer>,
     audio: Res<Audio>,
     mut audio_instances: ResMut<Assets<AudioInstance>>,
+    audio_sources: Res<Assets<AudioSource>>,
     mut state: ResMut<EditorState>,
     keyboard: Res<ButtonInput<KeyCode>>,
     time: Res<Time>,
 ) {
     let ctx = contexts.ctx_mut();
+    let frame_start_time = state.current_time;
+
+    let audio_len_ms = state
+        .audio_handle
+        .as_ref()
+        .and_then(|h| audio_sources.get(h))
+        .map(|source| source.sound.duration().as_millis() as u32);
 
     let prev_audio_file = state.meta.audioFile.clone();
     let prev_bpm = state.meta.bpm;
@@ -35,7 +57,6 @@ pub fn ui_system(
     let mut beatmap_settings_changed = false;
     let mut meta_settings_changed = false;
     let mut timing_points_changed = false;
-    let mut audio_file_changed = false;
 
     if state.is_playing {
         ctx.request_repaint();
@@ -44,37 +65,18 @@ pub fn ui_system(
     egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
         ui.horizontal(|ui| {
             if ui.button("📦 Load .rtm").clicked() {
-                if let Some(path) = rfd::FileDialog::new().add_filter("Map", &["rtm"]).pick_file() {
-                    match loader::load_rtm(&path) {
-                        Ok(pkg) => {
-                            let diff_count = pkg.difficulties.len();
-                            state.meta = pkg.meta.clone();
-                            state.rtm_package = Some(pkg);
-                            state.rtm_file_path = Some(path.clone());
-                            state.selected_difficulty = 0;
-
-                            if let Some(pkg) = &state.rtm_package {
-                                if let Some(first) = pkg.difficulties.get(0) {
-                                    state.beatmap = first.beatmap.clone();
-                                }
-                            }
+                if state.dirty {
+                    state.pending_action = Some(PendingAction::Load);
+                } else {
+                    load_rtm_dialog(&mut state);
+                }
+            }
 
-                            state.current_time = 0;
-                            state.is_playing = false;
-                            state.is_hold_mode = false;
-                            state.hold_starts.clear();
-                            state.audio_rel_path = None;
-                            state.audio_handle = None;
-                            state.audio_seek_request = Some(0);
-                            state.audio_instance = None;
-                            state.status = format!(
-                                "Loaded rtm: {} ({} diffs)",
-                                path.file_name().and_then(|s| s.to_str()).unwrap_or("<file>"),
-                                diff_count
-                            );
-                        }
-                        Err(err) => state.status = format!("Load .rtm failed: {}", err),
-                    }
+            if ui.button("🆕 New Mapset").clicked() {
+                if state.dirty {
+                    state.pending_action = Some(PendingAction::NewMapset);
+                } else {
+                    new_mapset_dialog(&mut state);
                 }
             }
 
@@ -100,23 +102,14 @@ pub fn ui_system(
                     });
 
                 if next_idx != state.selected_difficulty {
-                    // Save current edits into the currently selected difficulty, then swap in the new one
-                    let old_idx = state.selected_difficulty;
-                    let beatmap_snapshot = state.beatmap.clone();
-                    if let Some(pkg) = state.rtm_package.as_mut() {
-                        if let Some(cur) = pkg.difficulties.get_mut(old_idx) {
-                            cur.beatmap = beatmap_snapshot;
-                        }
-                    }
-                    state.selected_difficulty = next_idx;
-                    if let Some(pkg) = state.rtm_package.as_ref() {
-                        if let Some(sel) = pkg.difficulties.get(next_idx) {
-                            state.beatmap = sel.beatmap.clone();
-                        }
-                    }
+                    state.select_difficulty(next_idx);
                     state.status = "Switched difficulty".to_string();
                 }
 
+                if ui.button("🗂 Manage").on_hover_text("Add, rename, delete and reorder difficulties").clicked() {
+                    state.show_difficulties = !state.show_difficulties;
+                }
+
                 if ui.button("➕ Import Difficulty").clicked() {
                     if let Some(path) = rfd::FileDialog::new().add_filter("Difficulty", &["json"]).pick_file() {
                         match loader::import_difficulty_json(&path) {
@@ -126,67 +119,43 @@ pub fn ui_system(
                                     .and_then(|s| s.to_str())
                                     .unwrap_or("difficulty")
                                     .to_string();
-                                let mut filename = path
+                                let filename = path
                                     .file_name()
                                     .and_then(|s| s.to_str())
                                     .unwrap_or("difficulty.json")
                                     .to_string();
 
-                                if let Some(pkg) = &mut state.rtm_package {
-                                    // Ensure filename uniqueness within the package
-                                    let used: std::collections::HashSet<String> = pkg
-                                        .difficulties
-                                        .iter()
-                                        .map(|d| d.meta.filename.clone())
-                                        .collect();
-                                    if used.contains(&filename) {
-                                        let mut n = 2;
-                                        let base = stem.clone();
-                                        loop {
-                                            let candidate = format!("{}-{}.json", base, n);
-                                            if !used.contains(&candidate) {
-                                                filename = candidate;
-                                                break;
-                                            }
-                                            n += 1;
-                                        }
-                                    }
-
-                                    let display_name = if !beatmap.name.trim().is_empty() && beatmap.name != "New Beatmap" {
-                                        beatmap.name.clone()
-                                    } else {
-                                        stem
-                                    };
-
-                                    pkg.difficulties.push(loader::RtmDifficulty {
-                                        meta: data::MetaDifficulty {
-                                            name: display_name,
-                                            filename: filename.clone(),
-                                        },
-                                        beatmap,
-                                    });
-                                    let new_idx = pkg.difficulties.len().saturating_sub(1);
-                                    let new_meta_diffs: Vec<data::MetaDifficulty> = pkg
-                                        .difficulties
-                                        .iter()
-                                        .map(|d| d.meta.clone())
-                                        .collect();
-                                    let new_beatmap = pkg
-                                        .difficulties
-                                        .get(new_idx)
-                                        .map(|d| d.beatmap.clone())
-                                        .unwrap_or_else(crate::data::Beatmap::new);
-
-                                    // Keep meta in sync
-                                    state.meta.difficulties = new_meta_diffs;
-                                    state.selected_difficulty = new_idx;
-                                    state.beatmap = new_beatmap;
-
-                                    state.status = format!(
-                                        "Imported difficulty: {}",
-                                        path.file_name().and_then(|s| s.to_str()).unwrap_or("<file>")
-                                    );
-                                }
+                                let before = DifficultySnapshot::capture(&state.document());
+
+                                // Ensure filename uniqueness within the package
+                                let filename = match state.rtm_package.as_ref() {
+                                    Some(pkg) => loader::unique_difficulty_filename(
+                                        &before.difficulties,
+                                        &pkg.other_files,
+                                        &filename,
+                                    ),
+                                    None => filename,
+                                };
+                                let display_name = loader::imported_difficulty_name(&beatmap, &stem);
+
+                                let mut after = before.clone();
+                                after.difficulties.push(loader::RtmDifficulty {
+                                    meta: data::MetaDifficulty {
+                                        name: display_name,
+                                        filename,
+                                    },
+                                    beatmap,
+                                });
+                                after.selected = after.difficulties.len() - 1;
+
+                                // Restoring the snapshot also keeps meta.difficulties in sync
+                                state.apply_edit(Edit::SetDifficulties { before, after });
+                                state.hold_starts.clear();
+
+                                state.status = format!(
+                                    "Imported difficulty: {}",
+                                    path.file_name().and_then(|s| s.to_str()).unwrap_or("<file>")
+                                );
                             }
                             Err(err) => state.status = format!("Import failed: {}", err),
                         }
@@ -237,32 +206,50 @@ pub fn ui_system(
                 }
 
                 if ui.button("💾 Save .rtm").clicked() {
-                    let path = state.rtm_file_path.clone();
-                    let idx = state.selected_difficulty;
-                    let beatmap_snapshot = state.beatmap.clone();
-                    let meta_snapshot = state.meta.clone();
-                    if let (Some(pkg), Some(path)) = (state.rtm_package.as_mut(), path) {
-                        if let Some(cur) = pkg.difficulties.get_mut(idx) {
-                            cur.beatmap = beatmap_snapshot;
-                        }
-                        pkg.meta = meta_snapshot;
-                        match loader::save_rtm(&path, pkg) {
-                            Ok(()) => {
-                                state.status = format!(
-                                    "Saved rtm: {}",
-                                    path.file_name().and_then(|s| s.to_str()).unwrap_or("<file>")
-                                );
-                            }
-                            Err(err) => state.status = format!("Save .rtm failed: {}", err),
-                        }
-                    } else {
-                        state.status = "No .rtm loaded".to_string();
+                    save_with_validation(&mut state, audio_len_ms);
+                }
+
+                if ui.button("🔊 Samples").clicked() {
+                    state.show_samples = !state.show_samples;
+                }
+
+                if ui.button("🖼 Background").clicked() {
+                    state.show_background_window = !state.show_background_window;
+                }
+
+                if ui.button("📊 Stats").clicked() {
+                    state.show_stats = !state.show_stats;
+                }
+
+                if ui.button("🩺 Validate").clicked() {
+                    state.sync_package();
+                    if let Some(pkg) = state.rtm_package.as_ref() {
+                        let diagnostics = validation::validate_package(pkg, audio_len_ms);
+                        state.status = format!("Validation: {} problems", diagnostics.len());
+                        state.diagnostics = diagnostics;
                     }
+                    state.show_validation = true;
+                    state.validation_blocked_save = false;
                 }
             }
 
             ui.separator();
 
+            if ui
+                .add_enabled(state.history.can_undo(), egui::Button::new("↶ Undo"))
+                .clicked()
+            {
+                state.undo();
+            }
+            if ui
+                .add_enabled(state.history.can_redo(), egui::Button::new("↷ Redo"))
+                .clicked()
+            {
+                state.redo();
+            }
+
+            ui.separator();
+
             ui.label(format!("Time: {} ms", state.current_time));
             if ui.button("◀ -100ms").clicked() {
                 state.current_time = state.current_time.saturating_sub(100);
@@ -311,6 +298,40 @@ pub fn ui_system(
                 ctx.request_repaint();
             }
 
+            // Ctrl+Z undoes, Ctrl+Shift+Z (or Ctrl+Y) redoes. Text fields keep their own undo.
+            if !ctx.wants_keyboard_input() {
+                let ctrl = keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+                let shift = keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+                if ctrl && keyboard.just_pressed(KeyCode::KeyZ) {
+                    if shift {
+                        state.redo();
+                    } else {
+                        state.undo();
+                    }
+                } else if ctrl && keyboard.just_pressed(KeyCode::KeyY) {
+                    state.redo();
+                }
+
+                // Selection: Ctrl+A/C/X/V, Delete, arrows nudge by one snap step, Escape clears.
+                if ctrl && keyboard.just_pressed(KeyCode::KeyA) {
+                    selection::select_all(&mut state);
+                } else if ctrl && keyboard.just_pressed(KeyCode::KeyC) {
+                    selection::copy_selected(&mut state);
+                } else if ctrl && keyboard.just_pressed(KeyCode::KeyX) {
+                    selection::cut_selected(&mut state);
+                } else if ctrl && keyboard.just_pressed(KeyCode::KeyV) {
+                    selection::paste_at_playhead(&mut state);
+                } else if keyboard.any_just_pressed([KeyCode::Delete, KeyCode::Backspace]) {
+                    selection::delete_selected(&mut state);
+                } else if keyboard.just_pressed(KeyCode::ArrowLeft) {
+                    selection::nudge_selected(&mut state, -1);
+                } else if keyboard.just_pressed(KeyCode::ArrowRight) {
+                    selection::nudge_selected(&mut state, 1);
+                } else if keyboard.just_pressed(KeyCode::Escape) {
+                    state.selection.clear();
+                }
+            }
+
             ui.separator();
 
             let mode_label = if state.is_hold_mode { "HOLD MODE" } else { "TAP MODE" };
@@ -318,13 +339,77 @@ pub fn ui_system(
                 state.is_hold_mode = !state.is_hold_mode;
             }
 
+            let mut divisor = state.beat_divisor;
+            egui::ComboBox::from_label("Snap")
+                .selected_text(
+                    egui::RichText::new(format!("1/{}", divisor)).color(timeline_ui::tick_color(divisor)),
+                )
+                .show_ui(ui, |ui| {
+                    for &d in timing_util::BEAT_DIVISORS {
+                        ui.selectable_value(
+                            &mut divisor,
+                            d,
+                            egui::RichText::new(format!("1/{}", d)).color(timeline_ui::tick_color(d)),
+                        );
+                    }
+                });
+            state.beat_divisor = divisor;
+
+            if state.playtest.is_some() {
+                if ui.button("⏹ Stop Playtest").clicked() {
+                    state.is_playing = false;
+                    playtest::stop_playtest(&mut state);
+                }
+            } else if ui
+                .button("🎮 Playtest")
+                .on_hover_text("Play from the playhead and get judged on your key presses")
+                .clicked()
+            {
+                playtest::start_playtest(&mut state);
+            }
+
+            let mut rate = state.playback_rate;
+            egui::ComboBox::from_label("Speed")
+                .selected_text(format!("{}x", rate))
+                .width(64.0)
+                .show_ui(ui, |ui| {
+                    for &r in PLAYBACK_RATES {
+                        ui.selectable_value(&mut rate, r, format!("{}x", r));
+                    }
+                });
+            if rate != state.playback_rate {
+                state.playback_rate = rate;
+                state.status = format!("Playback speed {}x", rate);
+            }
+
+            ui.checkbox(&mut state.hitsounds_enabled, "🔔 Hitsounds");
+            ui.checkbox(&mut state.metronome_enabled, "🥁 Metronome");
+            if state.metronome_enabled {
+                ui.add(
+                    egui::Slider::new(&mut state.metronome_volume, 0.0..=1.0)
+                        .show_value(false)
+                        .text("Vol"),
+                );
+            }
+
             ui.label(format!("Notes: {}", state.beatmap.notes.len()));
+            if !state.selection.is_empty() {
+                ui.label(format!("Selected: {}", state.selection.len()));
+            }
 
             ui.separator();
+            if state.dirty {
+                ui.colored_label(egui::Color32::from_rgb(220, 190, 80), "● Unsaved");
+            }
             ui.label(&state.status);
         });
     });
 
+    // Settings edits are applied directly by the widgets, so snapshot first and record
+    // whatever changed once the panel has been drawn.
+    let meta_before = state.meta.clone();
+    let info_before = BeatmapInfo::of(&state.beatmap);
+
     // Sidebar for settings
     egui::SidePanel::right("sidebar")
         .resizable(true)
@@ -338,114 +423,105 @@ pub fn ui_system(
                 .outer_margin(egui::Margin::same(0.0)),
         )
         .show(ctx, |ui| {
-            ui.heading("Settings");
-
-            ui.group(|ui| {
-                ui.heading("Beatmap Settings");
-                if ui.text_edit_singleline(&mut state.beatmap.name).changed() {
-                    beatmap_settings_changed = true;
-                }
-                ui.label("Name");
-
-                let mut diff_val = state.beatmap.overallDifficulty;
-                if ui.add(egui::Slider::new(&mut diff_val, 0.0..=10.0).text("Overall Difficulty")).changed() {
-                    beatmap_settings_changed = true;
-                }
-                state.beatmap.overallDifficulty = diff_val.clamp(0.0, 10.0);
-            });
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.heading("Settings");
+
+                ui.group(|ui| {
+                    ui.heading("Beatmap Settings");
+                    if state.rtm_package.is_some() {
+                        // The name is also the difficulty's name and file name in the mapset, so
+                        // it is changed from the difficulty manager to keep them together.
+                        ui.horizontal(|ui| {
+                            ui.label(format!("Name: {}", state.beatmap.name));
+                            if ui.small_button("✏ Rename").clicked() {
+                                state.show_difficulties = true;
+                            }
+                        });
+                    } else {
+                        if ui.text_edit_singleline(&mut state.beatmap.name).changed() {
+                            beatmap_settings_changed = true;
+                        }
+                        ui.label("Name");
+                    }
 
-            ui.separator();
+                    let mut diff_val = state.beatmap.overallDifficulty;
+                    if ui.add(egui::Slider::new(&mut diff_val, 0.0..=10.0).text("Overall Difficulty")).changed() {
+                        beatmap_settings_changed = true;
+                    }
+                    state.beatmap.overallDifficulty = diff_val.clamp(0.0, 10.0);
+                });
 
-            ui.group(|ui| {
-                ui.heading("Meta (meta.json)");
+                ui.separator();
 
-                ui.label("Song Name");
-                if ui.text_edit_singleline(&mut state.meta.songName).changed() {
-                    meta_settings_changed = true;
-                }
+                ui.group(|ui| {
+                    rating_ui::draw_star_rating(ui, &mut state);
+                });
 
-                ui.label("Artist Name");
-                if ui.text_edit_singleline(&mut state.meta.artistName).changed() {
-                    meta_settings_changed = true;
-                }
+                ui.separator();
 
-                ui.label("Mapper");
-                if ui.text_edit_singleline(&mut state.meta.mapper).changed() {
-                    meta_settings_changed = true;
-                }
+                ui.group(|ui| {
+                    hitsound_ui::draw_hitsound_inspector(ui, &mut state, time.elapsed_seconds_f64());
+                });
 
-                ui.label("Audio File");
-                if ui.text_edit_singleline(&mut state.meta.audioFile).changed() {
-                    meta_settings_changed = true;
-                    audio_file_changed = true;
-                }
+                ui.separator();
 
-                if state.meta.timingPoints.is_empty() {
-                    ui.label("BPM");
-                    if ui.add(egui::DragValue::new(&mut state.meta.bpm).speed(0.1)).changed() {
+                ui.group(|ui| {
+                    if meta_ui::draw_meta(ui, &mut state, audio_len_ms) {
                         meta_settings_changed = true;
                     }
+                });
 
-                    ui.label("Offset (ms)");
-                    if ui.add(egui::DragValue::new(&mut state.meta.offset).speed(1)).changed() {
-                        meta_settings_changed = true;
-                    }
-                } else {
-                    let now_ms = state.current_time as i64;
-                    let mut active_idx: usize = 0;
-                    let mut best_offset: i64 = i64::MIN;
-                    for (i, tp) in state.meta.timingPoints.iter().enumerate() {
-                        if tp.offset <= now_ms && tp.offset >= best_offset {
-                            best_offset = tp.offset;
-                            active_idx = i;
-                        }
+                ui.separator();
+
+                ui.group(|ui| {
+                    if timing_ui::draw_timing_points(ui, &mut state) {
+                        timing_points_changed = true;
                     }
+                });
 
-                    ui.separator();
-                    ui.heading("Active Timing Point");
-                    ui.label(format!("Index: {}", active_idx));
+                ui.separator();
 
-                    let tp = &mut state.meta.timingPoints[active_idx];
+                ui.group(|ui| {
+                    suggestions::draw_suggestions(ui, &mut state);
+                });
 
-                    ui.label("BPM");
-                    if ui.add(egui::DragValue::new(&mut tp.bpm).speed(0.1)).changed() {
-                        timing_points_changed = true;
-                    }
+                ui.separator();
 
-                    ui.label("Offset (ms)");
-                    if ui.add(egui::DragValue::new(&mut tp.offset).speed(1)).changed() {
-                        timing_points_changed = true;
-                    }
+                ui.group(|ui| {
+                    record::draw_record_panel(ui, &mut state);
+                });
+
+                ui.separator();
 
-                    ui.label("Time Signature");
-                    let mut numer = tp.timeSignature[0];
-                    let mut denom = tp.timeSignature[1];
+                ui.group(|ui| {
+                    ui.heading("Saving");
                     ui.horizontal(|ui| {
-                        ui.label("Numer");
-                        if ui.add(egui::DragValue::new(&mut numer).speed(1)).changed() {
-                            timing_points_changed = true;
-                        }
-                        ui.label("Denom");
-                        if ui.add(egui::DragValue::new(&mut denom).speed(1)).changed() {
-                            timing_points_changed = true;
-                        }
+                        ui.add(egui::DragValue::new(&mut state.save_options.backups).clamp_range(0..=20));
+                        ui.label("Backups to keep");
                     });
-                    numer = numer.max(1);
-                    denom = denom.max(1);
-                    tp.timeSignature = [numer, denom];
-                }
+                    ui.weak("Saving keeps the previous versions as map.1.rtm.bak (newest) and up; 0 keeps none.");
+                });
             });
         });
 
-    if timing_points_changed {
-        state.meta.timingPoints.sort_by(|a, b| a.offset.cmp(&b.offset));
-        if let Some(first) = state.meta.timingPoints.first() {
-            let first_bpm = first.bpm;
-            let first_offset = first.offset;
-            state.meta.bpm = first_bpm;
-            state.meta.offset = first_offset;
-            meta_settings_changed = true;
-        }
+    let now_s = time.elapsed_seconds_f64();
+    if state.meta != meta_before {
+        let after = Box::new(state.meta.clone());
+        state.history.push_merging(Edit::SetMeta { before: Box::new(meta_before), after }, now_s);
+        state.mark_dirty();
+    }
+    let info_after = BeatmapInfo::of(&state.beatmap);
+    if info_after != info_before {
+        let difficulty = state.selected_difficulty;
+        state.history.push_merging(
+            Edit::SetBeatmapInfo {
+                difficulty,
+                before: info_before,
+                after: info_after,
+            },
+            now_s,
+        );
+        state.mark_dirty();
     }
 
     let settings_changed = beatmap_settings_changed
@@ -460,11 +536,12 @@ pub fn ui_system(
     if settings_changed {
         ctx.request_repaint();
 
-        if audio_file_changed || prev_audio_file != state.meta.audioFile {
+        if prev_audio_file != state.meta.audioFile {
             state.audio_rel_path = None;
             state.audio_handle = None;
             state.audio_instance = None;
             state.audio_seek_request = Some(state.current_time);
+            state.timing_candidates.clear();
             state.status = "Audio file changed (reloading audio)".to_string();
         }
 
@@ -473,6 +550,10 @@ pub fn ui_system(
         }
     }
 
+    draw_validation_window(ctx, &mut state);
+    draw_unsaved_window(ctx, &mut state, audio_len_ms);
+    draw_recovery_window(ctx, &mut state);
+
     egui::CentralPanel::default()
         .frame(
             egui::Frame::default()
@@ -488,12 +569,22 @@ pub fn ui_system(
 
             let keyboard_size = ui.available_size();
             let (keyboard_rect, _) = ui.allocate_exact_size(keyboard_size, egui::Sense::hover());
+            background::draw_background(ui, &state, keyboard_rect);
             keyboard_ui::draw_keyboard(ui, &mut state, keyboard_rect);
         });
 
+    // Anything above that moved the playhead was a seek, not playback. Systems that trigger
+    // sounds at map times skip this frame's movement so the jumped-over part stays silent.
+    state.playhead_jumped = state.current_time != frame_start_time;
+
     if state.is_playing {
-        let dt_ms = (time.delta_seconds_f64() * 1000.0).max(0.0);
-        state.current_time = state.current_time.saturating_add(dt_ms.round() as u32);
+        let dt_ms = (time.delta_seconds_f64() * 1000.0).max(0.0) * state.playback_rate;
+        let advance_ms = dt_ms + state.playback_frac_ms;
+        let whole_ms = advance_ms.floor();
+        state.playback_frac_ms = advance_ms - whole_ms;
+        state.current_time = state.current_time.saturating_add(whole_ms as u32);
+    } else {
+        state.playback_frac_ms = 0.0;
     }
 
     // Audio sync
@@ -515,13 +606,24 @@ pub fn ui_system(
     let should_have_instance = state.is_playing || state.audio_seek_request.is_some();
     if should_have_instance && state.audio_instance.is_none() {
         if let Some(handle) = state.audio_handle.clone() {
-            let instance = audio.play(handle).handle();
+            let instance = audio.play(handle).with_playback_rate(state.playback_rate).handle();
             state.audio_instance = Some(instance);
+            state.audio_applied_rate = state.playback_rate;
         }
     }
 
     if let Some(instance_handle) = state.audio_instance.clone() {
         if let Some(instance) = audio_instances.get_mut(&instance_handle) {
+            // A rate change also re-seeks, so audio and the editor clock restart together
+            // and the periodic resync below doesn't pull against the new rate.
+            if (state.audio_applied_rate - state.playback_rate).abs() > f64::EPSILON {
+                instance.set_playback_rate(state.playback_rate, AudioTween::default());
+                state.audio_applied_rate = state.playback_rate;
+                if state.audio_seek_request.is_none() {
+                    state.audio_seek_request = Some(state.current_time);
+                }
+            }
+
             let mut did_seek_this_frame = false;
             if let Some(ms) = state.audio_seek_request.take() {
                 let seconds = (ms as f64) / 1000.0;
@@ -558,3 +660,278 @@ pub fn ui_system(
         }
     }
 }
+
+/// Validates and saves. Errors block the save and open the validation window.
+/// Returns true if the package was written.
+fn save_with_validation(state: &mut EditorState, audio_len_ms: Option<u32>) -> bool {
+    state.sync_package();
+    let diagnostics = state
+        .rtm_package
+        .as_ref()
+        .map(|pkg| validation::validate_package(pkg, audio_len_ms))
+        .unwrap_or_default();
+    if validation::has_errors(&diagnostics) {
+        state.status = "Save blocked by validation errors".to_string();
+        state.diagnostics = diagnostics;
+        state.show_validation = true;
+        state.validation_blocked_save = true;
+        false
+    } else {
+        state.diagnostics = diagnostics;
+        save_package(state)
+    }
+}
+
+fn save_package(state: &mut EditorState) -> bool {
+    state.sync_package();
+    let Some(path) = state.rtm_file_path.clone() else {
+        state.status = "No .rtm loaded".to_string();
+        return false;
+    };
+    let Some(pkg) = state.rtm_package.as_ref() else {
+        state.status = "No .rtm loaded".to_string();
+        return false;
+    };
+    match loader::save_rtm_with(&path, pkg, &state.save_options) {
+        Ok(()) => {
+            state.status = format!(
+                "Saved rtm: {}",
+                path.file_name().and_then(|s| s.to_str()).unwrap_or("<file>")
+            );
+            state.dirty = false;
+            state.autosave_pending = false;
+            autosave::discard_for(&path);
+            true
+        }
+        Err(err) => {
+            state.status = format!("Save .rtm failed: {}", err);
+            false
+        }
+    }
+}
+
+fn load_rtm_dialog(state: &mut EditorState) {
+    if let Some(path) = rfd::FileDialog::new().add_filter("Map", &["rtm"]).pick_file() {
+        match loader::load_rtm(&path) {
+            Ok(pkg) => {
+                let diff_count = pkg.difficulties.len();
+                state.load_package(pkg, path.clone());
+                state.status = format!(
+                    "Loaded rtm: {} ({} diffs)",
+                    path.file_name().and_then(|s| s.to_str()).unwrap_or("<file>"),
+                    diff_count
+                );
+            }
+            Err(err) => state.status = format!("Load .rtm failed: {}", err),
+        }
+    }
+}
+
+fn new_mapset_dialog(state: &mut EditorState) {
+    if let Some(audio_path) = rfd::FileDialog::new()
+        .add_filter("Audio", &["mp3", "ogg", "wav", "flac"])
+        .pick_file()
+    {
+        let suggested = format!(
+            "{}.rtm",
+            audio_path.file_stem().and_then(|s| s.to_str()).unwrap_or("mapset")
+        );
+        if let Some(mut path) = rfd::FileDialog::new()
+            .add_filter("Map", &["rtm"])
+            .set_file_name(&suggested)
+            .save_file()
+        {
+            if path.extension().and_then(|e| e.to_str()).is_none() {
+                path.set_extension("rtm");
+            }
+
+            let options = state.save_options.clone();
+            match loader::new_rtm_package(&audio_path)
+                .and_then(|pkg| loader::save_rtm_with(&path, &pkg, &options).map(|()| pkg))
+            {
+                Ok(pkg) => {
+                    state.load_package(pkg, path.clone());
+                    state.status = format!(
+                        "Created rtm: {}",
+                        path.file_name().and_then(|s| s.to_str()).unwrap_or("<file>")
+                    );
+                }
+                Err(err) => state.status = format!("New mapset failed: {}", err),
+            }
+        }
+    }
+}
+
+fn draw_unsaved_window(ctx: &egui::Context, state: &mut EditorState, audio_len_ms: Option<u32>) {
+    let Some(action) = state.pending_action else {
+        return;
+    };
+    let mut choice: Option<bool> = None; // Some(true) = save first, Some(false) = discard
+    let mut cancel = false;
+
+    egui::Window::new("Unsaved changes")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            let what = match action {
+                PendingAction::Load => "loading another mapset",
+                PendingAction::NewMapset => "creating a new mapset",
+                PendingAction::Quit => "quitting",
+            };
+            ui.label(format!("Save your changes before {}?", what));
+            ui.horizontal(|ui| {
+                if ui.button("💾 Save").clicked() {
+                    choice = Some(true);
+                }
+                if ui.button("Discard").clicked() {
+                    choice = Some(false);
+                }
+                if ui.button("Cancel").clicked() {
+                    cancel = true;
+                }
+            });
+        });
+
+    if cancel {
+        state.pending_action = None;
+        return;
+    }
+    let Some(save_first) = choice else {
+        return;
+    };
+    state.pending_action = None;
+
+    if save_first {
+        if !save_with_validation(state, audio_len_ms) {
+            return;
+        }
+    } else if let Some(path) = state.rtm_file_path.clone() {
+        autosave::discard_for(&path);
+    }
+
+    match action {
+        PendingAction::Load => load_rtm_dialog(state),
+        PendingAction::NewMapset => new_mapset_dialog(state),
+        PendingAction::Quit => state.exit_requested = true,
+    }
+}
+
+fn draw_recovery_window(ctx: &egui::Context, state: &mut EditorState) {
+    let Some(snapshot) = state.recovery.clone() else {
+        return;
+    };
+    let mut restore = false;
+    let mut discard = false;
+
+    egui::Window::new("Recover unsaved work")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            let age_min = snapshot.age_s() / 60;
+            ui.label(format!(
+                "The editor closed with unsaved changes to {} (autosaved {} minutes ago).",
+                snapshot
+                    .source
+                    .file_name()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("<file>"),
+                age_min
+            ));
+            ui.horizontal(|ui| {
+                if ui.button("Restore").clicked() {
+                    restore = true;
+                }
+                if ui.button("Discard").clicked() {
+                    discard = true;
+                }
+            });
+        });
+
+    if restore {
+        state.recovery = None;
+        match snapshot.load() {
+            Ok(pkg) => {
+                state.load_package(pkg, snapshot.source.clone());
+                state.select_difficulty(snapshot.selected_difficulty);
+                // Restored work still needs saving to the original .rtm.
+                state.mark_dirty();
+                state.status = format!("Restored unsaved work for {}", snapshot.source.display());
+            }
+            Err(err) => state.status = format!("Restore failed: {}", err),
+        }
+    } else if discard {
+        state.recovery = None;
+        autosave::discard(&snapshot);
+    }
+}
+
+fn draw_validation_window(ctx: &egui::Context, state: &mut EditorState) {
+    let mut open = state.show_validation;
+    let mut goto: Option<(Option<usize>, Option<u32>)> = None;
+    let mut save_anyway = false;
+
+    egui::Window::new("🩺 Validation")
+        .open(&mut open)
+        .default_width(420.0)
+        .show(ctx, |ui| {
+            if state.diagnostics.is_empty() {
+                ui.label("No problems found.");
+            } else {
+                let errors = state
+                    .diagnostics
+                    .iter()
+                    .filter(|d| d.severity == Severity::Error)
+                    .count();
+                ui.label(format!(
+                    "{} errors, {} warnings",
+                    errors,
+                    state.diagnostics.len() - errors
+                ));
+            }
+
+            if state.validation_blocked_save {
+                ui.horizontal(|ui| {
+                    ui.colored_label(egui::Color32::from_rgb(230, 90, 90), "Saving was blocked.");
+                    if ui.button("Save anyway").clicked() {
+                        save_anyway = true;
+                    }
+                });
+            }
+
+            ui.separator();
+
+            egui::ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+                for d in &state.diagnostics {
+                    let color = match d.severity {
+                        Severity::Error => egui::Color32::from_rgb(230, 90, 90),
+                        Severity::Warning => egui::Color32::from_rgb(220, 190, 80),
+                    };
+                    ui.horizontal(|ui| {
+                        if d.time_ms.is_some() && ui.small_button("Go").clicked() {
+                            goto = Some((d.difficulty, d.time_ms));
+                        }
+                        ui.colored_label(color, d.to_string());
+                    });
+                }
+            });
+        });
+
+    state.show_validation = open;
+
+    if let Some((difficulty, time_ms)) = goto {
+        if let Some(idx) = difficulty {
+            state.select_difficulty(idx);
+        }
+        if let Some(t) = time_ms {
+            state.current_time = t;
+            state.audio_seek_request = Some(t);
+        }
+    }
+
+    if save_anyway {
+        state.validation_blocked_save = false;
+        save_package(state);
+    }
+}
@@ -0,0 +1,522 @@
+use crate::{
+    data::{Beatmap, KEYBOARD_LAYOUT},
+    hitsound_util,
+    loader::{find_entry_name, is_reserved_filename, RtmPackage},
+};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Index into `RtmPackage::difficulties`, or `None` for package-level problems.
+    pub difficulty: Option<usize>,
+    pub difficulty_name: Option<String>,
+    pub time_ms: Option<u32>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}: ", severity)?;
+        if let Some(name) = &self.difficulty_name {
+            write!(f, "[{}] ", name)?;
+        }
+        if let Some(t) = self.time_ms {
+            write!(f, "{}ms: ", t)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|d| d.severity == Severity::Error)
+}
+
+fn normalize_zip_path(p: &str) -> String {
+    p.trim().replace('\\', "/")
+}
+
+/// Checks a whole package. `audio_len_ms` enables the checks that need the song length;
+/// pass `None` when the audio hasn't been decoded.
+pub fn validate_package(pkg: &RtmPackage, audio_len_ms: Option<u32>) -> Vec<Diagnostic> {
+    let mut out = Vec::new();
+    let mut package_diag = |severity: Severity, message: String| {
+        out.push(Diagnostic {
+            severity,
+            difficulty: None,
+            difficulty_name: None,
+            time_ms: None,
+            message,
+        });
+    };
+
+    let meta = &pkg.meta;
+    // Resolved the way the editor finds files, which also matches by base name.
+    let in_package = |file: &str| find_entry_name(pkg, file).is_some();
+    let audio = normalize_zip_path(&meta.audioFile);
+    if audio.is_empty() {
+        package_diag(Severity::Error, "meta.json has no audioFile".to_string());
+    } else if !in_package(&audio) {
+        package_diag(
+            Severity::Error,
+            format!("audio file '{}' is not in the package", audio),
+        );
+    }
+
+    for bg in &meta.backgroundFiles {
+        let bg = normalize_zip_path(bg);
+        if !bg.is_empty() && !in_package(&bg) {
+            package_diag(
+                Severity::Warning,
+                format!("background file '{}' is not in the package", bg),
+            );
+        }
+    }
+
+    if let Some(video) = &meta.videoFile {
+        let video = normalize_zip_path(video);
+        if video.is_empty() {
+            package_diag(Severity::Warning, "videoFile is set but empty".to_string());
+        } else if !in_package(&video) {
+            package_diag(
+                Severity::Warning,
+                format!("video file '{}' is not in the package", video),
+            );
+        }
+    }
+
+    if meta
+        .timingPoints
+        .windows(2)
+        .any(|w| w[1].offset < w[0].offset)
+    {
+        package_diag(
+            Severity::Warning,
+            "timing points are not sorted by offset".to_string(),
+        );
+    }
+    for tp in &meta.timingPoints {
+        if tp.bpm <= 0.0 {
+            package_diag(
+                Severity::Error,
+                format!("timing point at {}ms has BPM {}", tp.offset, tp.bpm),
+            );
+        }
+    }
+
+    if meta.previewTime < 0 {
+        package_diag(
+            Severity::Warning,
+            format!("previewTime {}ms is negative", meta.previewTime),
+        );
+    } else if let Some(len) = audio_len_ms {
+        if meta.previewTime > len as i64 {
+            package_diag(
+                Severity::Warning,
+                format!(
+                    "previewTime {}ms is past the end of the audio ({}ms)",
+                    meta.previewTime, len
+                ),
+            );
+        }
+    }
+
+    let mut seen_filenames: BTreeSet<&str> = BTreeSet::new();
+    for d in &pkg.difficulties {
+        if !seen_filenames.insert(d.meta.filename.as_str()) {
+            package_diag(
+                Severity::Error,
+                format!("duplicate difficulty filename '{}'", d.meta.filename),
+            );
+        }
+        if is_reserved_filename(&pkg.other_files, &d.meta.filename) {
+            package_diag(
+                Severity::Error,
+                format!(
+                    "difficulty filename '{}' is also the name of meta.json or another file in the package",
+                    d.meta.filename
+                ),
+            );
+        }
+    }
+
+    for (idx, d) in pkg.difficulties.iter().enumerate() {
+        let bg = normalize_zip_path(&d.beatmap.bgFile);
+        let mut diags = validate_beatmap(&d.beatmap, audio_len_ms);
+        if !bg.is_empty() && !in_package(&bg) {
+            diags.push(Diagnostic {
+                severity: Severity::Warning,
+                difficulty: None,
+                difficulty_name: None,
+                time_ms: None,
+                message: format!("bgFile '{}' is not in the package", bg),
+            });
+        }
+        for message in hitsound_util::sample_set_problems(pkg, meta.hasCustomHitsounds, &d.beatmap) {
+            diags.push(Diagnostic {
+                severity: Severity::Warning,
+                difficulty: None,
+                difficulty_name: None,
+                time_ms: None,
+                message,
+            });
+        }
+        for mut diag in diags {
+            diag.difficulty = Some(idx);
+            diag.difficulty_name = Some(d.meta.name.clone());
+            out.push(diag);
+        }
+    }
+
+    out
+}
+
+/// Note-level checks for one difficulty. The returned diagnostics have no difficulty set.
+pub fn validate_beatmap(beatmap: &Beatmap, audio_len_ms: Option<u32>) -> Vec<Diagnostic> {
+    let mut out = Vec::new();
+    let mut note_diag = |severity: Severity, time_ms: u32, message: String| {
+        out.push(Diagnostic {
+            severity,
+            difficulty: None,
+            difficulty_name: None,
+            time_ms: Some(time_ms),
+            message,
+        });
+    };
+
+    let valid_keys: BTreeSet<String> = KEYBOARD_LAYOUT
+        .iter()
+        .flat_map(|row| row.iter())
+        .map(|k| k.to_lowercase())
+        .collect();
+
+    // (start, end) spans per key, for the overlap check.
+    let mut spans: BTreeMap<String, Vec<(u32, u32)>> = BTreeMap::new();
+
+    for n in &beatmap.notes {
+        let start = n.get_start_time();
+        let key = n.key.to_lowercase();
+
+        if !valid_keys.contains(&key) {
+            note_diag(
+                Severity::Error,
+                start,
+                format!("key '{}' is not on the keyboard layout", n.key),
+            );
+        }
+
+        match n.note_type.as_str() {
+            "hold" => match (n.startTime, n.endTime) {
+                (Some(s), Some(e)) if e <= s => note_diag(
+                    Severity::Error,
+                    start,
+                    format!(
+                        "hold on '{}' ends at {}ms, not after its start",
+                        n.key.to_uppercase(),
+                        e
+                    ),
+                ),
+                (Some(_), Some(_)) => {}
+                _ => note_diag(
+                    Severity::Error,
+                    start,
+                    format!(
+                        "hold on '{}' is missing startTime or endTime",
+                        n.key.to_uppercase()
+                    ),
+                ),
+            },
+            "tap" => {
+                if n.time.is_none() {
+                    note_diag(
+                        Severity::Error,
+                        start,
+                        format!("tap on '{}' has no time", n.key.to_uppercase()),
+                    );
+                }
+                if n.startTime.is_some() || n.endTime.is_some() {
+                    note_diag(
+                        Severity::Warning,
+                        start,
+                        format!(
+                            "tap on '{}' carries startTime/endTime",
+                            n.key.to_uppercase()
+                        ),
+                    );
+                }
+            }
+            other => note_diag(
+                Severity::Error,
+                start,
+                format!("unknown note type '{}'", other),
+            ),
+        }
+
+        if let Some(len) = audio_len_ms {
+            if n.get_end_time() > len {
+                note_diag(
+                    Severity::Error,
+                    start,
+                    format!(
+                        "note on '{}' ends after the audio ({}ms)",
+                        n.key.to_uppercase(),
+                        len
+                    ),
+                );
+            }
+        }
+
+        spans
+            .entry(key)
+            .or_default()
+            .push((start, n.get_end_time()));
+    }
+
+    for (key, mut key_spans) in spans {
+        key_spans.sort();
+        for w in key_spans.windows(2) {
+            let (_, prev_end) = w[0];
+            let (start, _) = w[1];
+            if start <= prev_end {
+                note_diag(
+                    Severity::Error,
+                    start,
+                    format!("notes on '{}' overlap", key.to_uppercase()),
+                );
+            }
+        }
+    }
+
+    out.sort_by_key(|d| d.time_ms);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{BeatmapNote, Meta, MetaDifficulty, TimingPoint};
+    use crate::loader::RtmDifficulty;
+    use std::collections::BTreeMap;
+
+    fn package(files: &[&str]) -> RtmPackage {
+        RtmPackage {
+            meta: Meta::default(),
+            difficulties: Vec::new(),
+            other_files: files.iter().map(|f| (f.to_string(), Vec::new())).collect::<BTreeMap<_, _>>(),
+        }
+    }
+
+    fn not_in_package(pkg: &RtmPackage) -> Vec<String> {
+        validate_package(pkg, None)
+            .into_iter()
+            .map(|d| d.message)
+            .filter(|m| m.contains("is not in the package"))
+            .collect()
+    }
+
+    #[test]
+    fn files_in_another_folder_resolve_by_base_name() {
+        let mut pkg = package(&["song.mp3", "bg.png", "clip.mp4"]);
+        pkg.meta.audioFile = "audio\\song.mp3".to_string();
+        pkg.meta.backgroundFiles = vec!["images/bg.png".to_string()];
+        pkg.meta.videoFile = Some("video/clip.mp4".to_string());
+        assert!(not_in_package(&pkg).is_empty());
+    }
+
+    #[test]
+    fn difficulty_files_cannot_shadow_other_files() {
+        let mut pkg = package(&["song.mp3", "easy.json"]);
+        pkg.meta.audioFile = "song.mp3".to_string();
+        for filename in ["easy.json", "meta.json", "hard.json"] {
+            pkg.difficulties.push(RtmDifficulty {
+                meta: MetaDifficulty {
+                    name: filename.to_string(),
+                    filename: filename.to_string(),
+                },
+                beatmap: Beatmap::new(),
+            });
+        }
+        let clashes: Vec<String> = validate_package(&pkg, None)
+            .into_iter()
+            .filter(|d| d.severity == Severity::Error && d.message.contains("also the name of"))
+            .map(|d| d.message)
+            .collect();
+        assert_eq!(clashes.len(), 2);
+        assert!(clashes[0].contains("easy.json") && clashes[1].contains("meta.json"));
+    }
+
+    #[test]
+    fn missing_files_are_reported() {
+        let mut pkg = package(&["song.mp3"]);
+        pkg.meta.audioFile = "other.mp3".to_string();
+        pkg.meta.backgroundFiles = vec!["bg.png".to_string()];
+        pkg.meta.videoFile = Some("clip.mp4".to_string());
+        assert_eq!(not_in_package(&pkg).len(), 3);
+    }
+
+    fn tap(key: &str, time: u32) -> BeatmapNote {
+        BeatmapNote::tap(key.to_string(), time)
+    }
+
+    fn hold(key: &str, start: u32, end: u32) -> BeatmapNote {
+        BeatmapNote::hold(key.to_string(), start, end)
+    }
+
+    /// The single diagnostic `notes` produce, as (severity, time, message).
+    fn only_diag(notes: Vec<BeatmapNote>, audio_len_ms: Option<u32>) -> (Severity, Option<u32>, String) {
+        let beatmap = Beatmap {
+            notes,
+            ..Beatmap::new()
+        };
+        let diags = validate_beatmap(&beatmap, audio_len_ms);
+        assert_eq!(diags.len(), 1, "{:?}", diags);
+        let d = diags.into_iter().next().unwrap();
+        (d.severity, d.time_ms, d.message)
+    }
+
+    /// A package with nothing wrong in it: audio, background, one sorted timing point and
+    /// a difficulty with a tap and a hold.
+    fn clean_package() -> RtmPackage {
+        let mut pkg = package(&["song.mp3", "background.png"]);
+        pkg.meta.audioFile = "song.mp3".to_string();
+        pkg.meta.previewTime = 1_000;
+        pkg.meta.timingPoints = vec![TimingPoint {
+            id: 0.0,
+            time: 0.0,
+            bpm: 120.0,
+            offset: 0,
+            timeSignature: [4, 4],
+        }];
+        pkg.difficulties.push(RtmDifficulty {
+            meta: MetaDifficulty {
+                name: "Easy".to_string(),
+                filename: "easy.json".to_string(),
+            },
+            beatmap: Beatmap {
+                notes: vec![tap("a", 500), hold("s", 1_000, 2_000), tap("s", 2_500)],
+                ..Beatmap::new()
+            },
+        });
+        pkg
+    }
+
+    #[test]
+    fn a_clean_map_has_no_diagnostics() {
+        let diags = validate_package(&clean_package(), Some(10_000));
+        assert!(diags.is_empty(), "{:?}", diags);
+    }
+
+    #[test]
+    fn overlapping_notes_on_one_key() {
+        let (severity, time, message) = only_diag(vec![hold("a", 1_000, 2_000), tap("A", 1_500)], None);
+        assert_eq!((severity, time), (Severity::Error, Some(1_500)));
+        assert!(message.contains("overlap"));
+    }
+
+    #[test]
+    fn hold_ending_at_or_before_its_start() {
+        let (severity, time, message) = only_diag(vec![hold("a", 1_000, 1_000)], None);
+        assert_eq!((severity, time), (Severity::Error, Some(1_000)));
+        assert!(message.contains("not after its start"));
+    }
+
+    #[test]
+    fn hold_missing_its_start_or_end() {
+        let mut no_end = hold("a", 1_000, 2_000);
+        no_end.endTime = None;
+        let (severity, time, message) = only_diag(vec![no_end], None);
+        assert_eq!((severity, time), (Severity::Error, Some(1_000)));
+        assert!(message.contains("missing startTime or endTime"));
+    }
+
+    #[test]
+    fn tap_carrying_hold_times() {
+        let mut note = tap("a", 1_000);
+        note.endTime = Some(1_200);
+        let (severity, time, message) = only_diag(vec![note], None);
+        assert_eq!((severity, time), (Severity::Warning, Some(1_000)));
+        assert!(message.contains("carries startTime/endTime"));
+    }
+
+    #[test]
+    fn key_off_the_layout() {
+        let (severity, time, message) = only_diag(vec![tap("1", 750)], None);
+        assert_eq!((severity, time), (Severity::Error, Some(750)));
+        assert!(message.contains("not on the keyboard layout"));
+    }
+
+    #[test]
+    fn note_past_the_end_of_the_audio() {
+        let (severity, time, message) = only_diag(vec![hold("a", 8_000, 12_000)], Some(10_000));
+        assert_eq!((severity, time), (Severity::Error, Some(8_000)));
+        assert!(message.contains("ends after the audio"));
+        // Without the song length the check is skipped.
+        let beatmap = Beatmap {
+            notes: vec![hold("a", 8_000, 12_000)],
+            ..Beatmap::new()
+        };
+        assert!(validate_beatmap(&beatmap, None).is_empty());
+    }
+
+    /// The diagnostics of `pkg` that aren't about a difficulty or a note.
+    fn package_diags(pkg: &RtmPackage, audio_len_ms: Option<u32>) -> Vec<(Severity, Option<u32>, String)> {
+        validate_package(pkg, audio_len_ms)
+            .into_iter()
+            .filter(|d| d.difficulty.is_none())
+            .map(|d| (d.severity, d.time_ms, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn unsorted_timing_points() {
+        let mut pkg = clean_package();
+        let mut later = pkg.meta.timingPoints[0].clone();
+        later.offset = 5_000;
+        pkg.meta.timingPoints.insert(0, later);
+        let diags = package_diags(&pkg, None);
+        assert_eq!(diags.len(), 1, "{:?}", diags);
+        assert_eq!((diags[0].0, diags[0].1), (Severity::Warning, None));
+        assert!(diags[0].2.contains("not sorted"));
+    }
+
+    #[test]
+    fn preview_time_outside_the_song() {
+        let mut pkg = clean_package();
+        pkg.meta.previewTime = -1;
+        let diags = package_diags(&pkg, Some(10_000));
+        assert_eq!(diags.len(), 1, "{:?}", diags);
+        assert_eq!((diags[0].0, diags[0].1), (Severity::Warning, None));
+        assert!(diags[0].2.contains("is negative"));
+
+        pkg.meta.previewTime = 10_001;
+        let diags = package_diags(&pkg, Some(10_000));
+        assert_eq!(diags.len(), 1, "{:?}", diags);
+        assert_eq!((diags[0].0, diags[0].1), (Severity::Warning, None));
+        assert!(diags[0].2.contains("past the end of the audio"));
+        // Without the song length only the negative check applies.
+        assert!(package_diags(&pkg, None).is_empty());
+    }
+
+    #[test]
+    fn duplicate_difficulty_filenames() {
+        let mut pkg = clean_package();
+        let mut copy = pkg.difficulties[0].clone();
+        copy.meta.name = "Easy (copy)".to_string();
+        pkg.difficulties.push(copy);
+        let diags = package_diags(&pkg, None);
+        assert_eq!(diags.len(), 1, "{:?}", diags);
+        assert_eq!((diags[0].0, diags[0].1), (Severity::Error, None));
+        assert!(diags[0].2.contains("duplicate difficulty filename 'easy.json'"));
+    }
+}
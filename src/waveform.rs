@@ -0,0 +1,131 @@
+use crate::editor_state::EditorState;
+use bevy::prelude::*;
+use bevy::tasks::{block_on, poll_once, AsyncComputeTaskPool, Task};
+use bevy_kira_audio::AudioSource;
+use kira::sound::static_sound::StaticSoundData;
+use std::sync::Arc;
+
+/// Frames summarised by one bucket of the finest level.
+const BASE_BUCKET_FRAMES: usize = 128;
+
+#[derive(Clone, Copy, Default)]
+pub struct Bucket {
+    pub min: f32,
+    pub max: f32,
+    pub rms: f32,
+}
+
+impl Bucket {
+    fn merge(a: Bucket, b: Bucket) -> Bucket {
+        Bucket {
+            min: a.min.min(b.min),
+            max: a.max.max(b.max),
+            rms: ((a.rms * a.rms + b.rms * b.rms) * 0.5).sqrt(),
+        }
+    }
+}
+
+/// Peak and RMS levels of the song at several resolutions. Each level has buckets twice
+/// as long as the one before, so any zoom can be drawn from a level with about one bucket
+/// per pixel.
+pub struct Waveform {
+    sample_rate: u32,
+    levels: Vec<Vec<Bucket>>,
+}
+
+impl Waveform {
+    fn build(sound: &StaticSoundData) -> Waveform {
+        let base: Vec<Bucket> = sound
+            .frames
+            .chunks(BASE_BUCKET_FRAMES)
+            .map(|chunk| {
+                let mut b = Bucket {
+                    min: f32::MAX,
+                    max: f32::MIN,
+                    rms: 0.0,
+                };
+                let mut sum_sq = 0.0;
+                for frame in chunk {
+                    let s = (frame.left + frame.right) * 0.5;
+                    b.min = b.min.min(s);
+                    b.max = b.max.max(s);
+                    sum_sq += s * s;
+                }
+                b.rms = (sum_sq / chunk.len() as f32).sqrt();
+                b
+            })
+            .collect();
+
+        let mut levels = vec![base];
+        while let Some(prev) = levels.last().filter(|l| l.len() > 1) {
+            let next = prev
+                .chunks(2)
+                .map(|pair| pair.iter().copied().reduce(Bucket::merge).unwrap_or_default())
+                .collect();
+            levels.push(next);
+        }
+        Waveform {
+            sample_rate: sound.sample_rate.max(1),
+            levels,
+        }
+    }
+
+    fn bucket_ms(&self, level: usize) -> f32 {
+        (BASE_BUCKET_FRAMES << level) as f32 * 1000.0 / self.sample_rate as f32
+    }
+
+    /// The combined bucket for `[from_ms, to_ms)`, read from the coarsest level that still
+    /// has buckets no longer than the span.
+    pub fn range(&self, from_ms: f32, to_ms: f32) -> Option<Bucket> {
+        let span = (to_ms - from_ms).max(0.0);
+        let level = (0..self.levels.len())
+            .rev()
+            .find(|&l| self.bucket_ms(l) <= span)
+            .unwrap_or(0);
+        let buckets = &self.levels[level];
+        let bucket_ms = self.bucket_ms(level);
+        let first = (from_ms.max(0.0) / bucket_ms) as usize;
+        let last = ((to_ms.max(0.0) / bucket_ms).ceil() as usize).max(first + 1);
+        buckets
+            .get(first..last.min(buckets.len()))?
+            .iter()
+            .copied()
+            .reduce(Bucket::merge)
+    }
+}
+
+/// The waveform being computed for the loaded song, if any.
+#[derive(Resource, Default)]
+pub struct WaveformLoader {
+    source: Option<AssetId<AudioSource>>,
+    task: Option<Task<Waveform>>,
+}
+
+/// Builds `EditorState::waveform` on the async compute pool once the song has decoded,
+/// and drops it when the song changes.
+pub fn waveform_system(
+    mut state: ResMut<EditorState>,
+    mut loader: ResMut<WaveformLoader>,
+    sources: Res<Assets<AudioSource>>,
+) {
+    let id = state.audio_handle.as_ref().map(|h| h.id());
+    if id != loader.source {
+        loader.source = None;
+        loader.task = None;
+        state.waveform = None;
+        // Start once the asset has loaded; until then keep checking.
+        if let Some(source) = id.and_then(|id| sources.get(id)) {
+            let sound = source.sound.clone();
+            let task = AsyncComputeTaskPool::get().spawn(async move { Waveform::build(&sound) });
+            loader.task = Some(task);
+            loader.source = id;
+        }
+    }
+
+    if let Some(task) = loader.task.as_mut() {
+        if let Some(waveform) = block_on(poll_once(task)) {
+            state.waveform = Some(Arc::new(waveform));
+            loader.task = None;
+        }
+    }
+}